@@ -0,0 +1,40 @@
+//! Compares sequential and parallel polynomial evaluation. Run with
+//!
+//!   cargo bench --features parallel
+
+#![feature(test)]
+
+extern crate test;
+
+use stark_102::{field::BaseField, poly::Polynomial};
+use test::Bencher;
+
+const DEGREE: usize = 1024;
+const DOMAIN_SIZE: usize = 2048;
+
+/// Our field only has 17 elements, so the coefficients and domain elements
+/// repeat; this doesn't matter to measure the cost of evaluation.
+fn synthetic_poly_and_domain() -> (Polynomial, Vec<BaseField>) {
+    let coefficients = (0..=DEGREE)
+        .map(|i| BaseField::from((i % 17) as u8))
+        .collect();
+    let domain = (0..DOMAIN_SIZE)
+        .map(|i| BaseField::from((i % 17) as u8))
+        .collect();
+
+    (Polynomial::new(coefficients), domain)
+}
+
+#[bench]
+fn eval_domain(b: &mut Bencher) {
+    let (poly, domain) = synthetic_poly_and_domain();
+
+    b.iter(|| poly.eval_domain(&domain));
+}
+
+#[bench]
+fn eval_domain_par(b: &mut Bencher) {
+    let (poly, domain) = synthetic_poly_and_domain();
+
+    b.iter(|| poly.eval_domain_par(&domain));
+}
@@ -1,38 +1,178 @@
-use blake3::{hash, Hash, Hasher};
+use std::cmp::min;
 
-use crate::field::BaseField;
+use blake3::Hash;
 
-/// The value to use to initialize the randomness of the channel. Normally, the
-/// channel is initialized with the public inputs, but we don't have any.
+use crate::{field::BaseField, hash::HashAlgorithm};
+
+/// The value to use to initialize the randomness of a channel created without
+/// public inputs (see `Channel::new_empty()`).
 const CHANNEL_SALT: [u8; 1] = [42u8];
 
+/// A message exchanged through the `Channel`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TranscriptMessage {
+    /// A commitment sent by the prover (see `Channel::commit()`)
+    Commit(Hash),
+    /// A random element drawn by the verifier (see `Channel::random_element()`)
+    Challenge(BaseField),
+    /// A random integer drawn by the verifier (see `Channel::random_u64()`)
+    Integer(u64),
+    /// Random bytes drawn by the verifier (see `Channel::random_bytes()`)
+    Bytes(Vec<u8>),
+}
+
+/// Every message exchanged through a `Channel`, in order. Comparing the
+/// transcripts of the prover's and verifier's channels pinpoints the step
+/// where they diverge.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ChannelTranscript {
+    pub messages: Vec<TranscriptMessage>,
+}
+
+impl ChannelTranscript {
+    /// Returns the index of the first message that differs between the two
+    /// transcripts (or that is only in one of them), or `None` if they are
+    /// the same.
+    pub fn first_divergence(&self, other: &ChannelTranscript) -> Option<usize> {
+        let common_len = self.messages.len().min(other.messages.len());
+
+        (0..common_len)
+            .find(|&i| self.messages[i] != other.messages[i])
+            .or((self.messages.len() != other.messages.len()).then_some(common_len))
+    }
+}
+
 /// A Channel implements the Fiat-Shamir transform. See the README for more
 /// information.
 #[derive(Debug)]
 pub struct Channel {
+    hash_algorithm: HashAlgorithm,
     current_hash: Hash,
     count: u64,
     commitments: Vec<Hash>,
+    /// Only recorded for channels created with `new_recording()`
+    transcript: Option<ChannelTranscript>,
 }
 
 impl Channel {
-    pub fn new() -> Self {
+    /// Creates a channel whose randomness is initialized with the hash of the
+    /// public inputs. This binds everything drawn from the channel (and hence
+    /// the proof) to the statement being proven.
+    pub fn new(pub_inputs: &[u8]) -> Self {
+        Self::new_with_hash(HashAlgorithm::default(), pub_inputs)
+    }
+
+    /// Same as `new()`, but uses `hash_algorithm` instead of the default
+    /// (blake3) to derive the randomness.
+    pub fn new_with_hash(hash_algorithm: HashAlgorithm, pub_inputs: &[u8]) -> Self {
+        Self {
+            hash_algorithm,
+            current_hash: hash_algorithm.hash(pub_inputs),
+            count: 0,
+            commitments: Vec::new(),
+            transcript: None,
+        }
+    }
+
+    /// Same as `new()`, but the channel records every message exchanged
+    /// through it (see `transcript()`). This is meant for debugging.
+    pub fn new_recording(pub_inputs: &[u8]) -> Self {
+        Self {
+            transcript: Some(ChannelTranscript::default()),
+            ..Self::new(pub_inputs)
+        }
+    }
+
+    /// Creates a channel without public inputs, whose randomness is initialized
+    /// with `CHANNEL_SALT` instead.
+    pub fn new_empty() -> Self {
+        Self::new(&CHANNEL_SALT)
+    }
+
+    /// Creates a channel whose randomness is initialized with `salt` instead of
+    /// the default `CHANNEL_SALT`. This is the same as `new()`, for callers
+    /// that seed the channel with something other than public inputs.
+    pub fn with_salt(salt: &[u8]) -> Self {
+        Self::new(salt)
+    }
+
+    /// Mixes `nonce` into the channel state. This is used for proof-of-work
+    /// grinding, where the prover searches for a nonce such that the resulting
+    /// channel state satisfies some property.
+    ///
+    /// Note: unlike `commit()`, the nonce is not recorded as a commitment.
+    pub fn incorporate_nonce(&mut self, nonce: u64) {
+        self.current_hash = self
+            .hash_algorithm
+            .hash_all(&[self.current_hash.as_bytes(), &nonce.to_le_bytes()]);
+    }
+
+    /// Performs proof-of-work: searches for the smallest nonce such that
+    /// `hash(current_hash || nonce)` starts with `bits` zero bits, and mixes it
+    /// into the channel state (see `incorporate_nonce()`). This takes `2^bits`
+    /// hashes on average, which a cheating prover has to pay again every time
+    /// it tries new values to draw; hence, grinding adds `bits` bits of
+    /// security.
+    ///
+    /// Panics if `bits` is greater than 64.
+    pub fn grind(&mut self, bits: u32) -> u64 {
+        let nonce = (0..u64::MAX)
+            .find(|nonce| self.is_valid_grind_nonce(*nonce, bits))
+            .expect("a nonce exists with overwhelming probability");
+
+        self.incorporate_nonce(nonce);
+
+        nonce
+    }
+
+    /// Checks the proof-of-work performed by `grind()` with a single hash, and
+    /// mixes `nonce` into the channel state if it is valid.
+    ///
+    /// Panics if `bits` is greater than 64.
+    pub fn verify_grind(&mut self, nonce: u64, bits: u32) -> bool {
+        if !self.is_valid_grind_nonce(nonce, bits) {
+            return false;
+        }
+
+        self.incorporate_nonce(nonce);
+
+        true
+    }
+
+    fn is_valid_grind_nonce(&self, nonce: u64, bits: u32) -> bool {
+        assert!(bits <= 64, "can't grind {bits} bits");
+
+        let hash = self
+            .hash_algorithm
+            .hash_all(&[self.current_hash.as_bytes(), &nonce.to_le_bytes()]);
+        let hash_first_8_bytes: [u8; 8] = hash.as_bytes()[0..8].try_into().unwrap();
+
+        u64::from_be_bytes(hash_first_8_bytes).leading_zeros() >= bits
+    }
+
+    /// Creates a new channel, independent from this one, whose randomness is
+    /// initialized with `hash(current_hash || label)`. The child channel is
+    /// bound to the transcript so far, but shares no state with its parent.
+    pub fn fork(&self, label: &[u8]) -> Channel {
         Self {
-            current_hash: hash(&CHANNEL_SALT),
+            hash_algorithm: self.hash_algorithm,
+            current_hash: self
+                .hash_algorithm
+                .hash_all(&[self.current_hash.as_bytes(), label]),
             count: 0,
             commitments: Vec::new(),
+            transcript: None,
         }
     }
 
     /// Captures a message sent from the prover to the verifier.
     pub fn commit(&mut self, commitment: Hash) {
         self.commitments.push(commitment);
+        self.record(TranscriptMessage::Commit(commitment));
 
-        let mut hasher = Hasher::new();
-        hasher.update(self.current_hash.as_bytes());
-        hasher.update(commitment.as_bytes());
-
-        self.current_hash = hasher.finalize();
+        self.current_hash = self
+            .hash_algorithm
+            .hash_all(&[self.current_hash.as_bytes(), commitment.as_bytes()]);
     }
 
     /// Draws a random element from `BaseField` (i.e. a number between 0 and 16).
@@ -43,32 +183,109 @@ impl Channel {
         let ret_element: BaseField = i32::from_le_bytes(hash_first_4_bytes).into();
 
         self.rehash_after_draw();
+        self.record(TranscriptMessage::Challenge(ret_element));
 
         ret_element
     }
 
+    /// Draws `n` distinct nonzero random elements from `BaseField`, by drawing
+    /// elements until we have `n` different nonzero ones (i.e. zeros and
+    /// collisions are resampled).
+    ///
+    /// Captures messages sent from the verifier to the prover.
+    ///
+    /// Panics if `n` is greater than 16, the number of nonzero elements.
+    pub fn draw_distinct_nonzero_elements(&mut self, n: usize) -> Vec<BaseField> {
+        assert!(n <= 16, "can't draw {n} distinct nonzero elements");
+
+        let mut elements = Vec::with_capacity(n);
+        while elements.len() < n {
+            let element = self.random_element();
+
+            if element != BaseField::zero() && !elements.contains(&element) {
+                elements.push(element);
+            }
+        }
+
+        elements
+    }
+
     /// Draws a random integer (uniformly distributed) in the range [0, upper_bound-1].
     ///
     /// Captures a message sent from the verifier to the prover.
     pub fn random_integer(&mut self, upper_bound: u8) -> u8 {
-        let hash_first_byte: [u8; 1] = self.current_hash.as_bytes()[0..1].try_into().unwrap();
-        let ret_element = u8::from_le_bytes(hash_first_byte) % upper_bound;
+        self.random_u64(upper_bound as u64) as u8
+    }
+
+    /// Same as `random_integer()`, for domains with more than 255 elements.
+    ///
+    /// Note: reducing a 64-bit value modulo `upper_bound` is biased towards
+    /// small values, but the bias is negligible as long as `upper_bound` is
+    /// much smaller than `2^64`.
+    ///
+    /// Captures a message sent from the verifier to the prover.
+    pub fn random_u64(&mut self, upper_bound: u64) -> u64 {
+        let hash_first_8_bytes: [u8; 8] = self.current_hash.as_bytes()[0..8].try_into().unwrap();
+        let ret_element = u64::from_le_bytes(hash_first_8_bytes) % upper_bound;
 
         self.rehash_after_draw();
+        self.record(TranscriptMessage::Integer(ret_element));
 
         ret_element
     }
 
+    /// Draws `len` pseudo-random bytes. Each block of 32 bytes is the current
+    /// hash, which is then advanced as for any other draw. Hence, drawing 64
+    /// bytes gives the same bytes as drawing 32 bytes twice.
+    pub fn random_bytes(&mut self, len: usize) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(len);
+        while bytes.len() < len {
+            let num_bytes = min(len - bytes.len(), blake3::OUT_LEN);
+            bytes.extend_from_slice(&self.current_hash.as_bytes()[0..num_bytes]);
+
+            self.rehash_after_draw();
+        }
+        self.record(TranscriptMessage::Bytes(bytes.clone()));
+
+        bytes
+    }
+
     /// this is an arbitrary way to change the current hash, so that we can call
     /// `random_element()` multiple times and always get a different one
     fn rehash_after_draw(&mut self) {
-        let mut hasher = Hasher::new();
-        hasher.update(self.current_hash.as_bytes());
-        hasher.update(&self.count.to_le_bytes());
+        self.current_hash = self
+            .hash_algorithm
+            .hash_all(&[self.current_hash.as_bytes(), &self.count.to_le_bytes()]);
 
         self.count += 1;
+    }
+
+    /// Returns every message exchanged so far, if the channel was created with
+    /// `new_recording()`.
+    pub fn transcript(&self) -> Option<&ChannelTranscript> {
+        self.transcript.as_ref()
+    }
+
+    fn record(&mut self, message: TranscriptMessage) {
+        if let Some(transcript) = self.transcript.as_mut() {
+            transcript.messages.push(message);
+        }
+    }
 
-        self.current_hash = hasher.finalize();
+    /// Hashes together all the commitments made so far, in order. This is a
+    /// fingerprint of the transcript, which can be used to identify a proof.
+    pub fn transcript_hash(&self) -> Hash {
+        let commitments: Vec<&[u8]> = self
+            .commitments
+            .iter()
+            .map(|commitment| commitment.as_bytes().as_slice())
+            .collect();
+
+        self.hash_algorithm.hash_all(&commitments)
+    }
+
+    pub fn hash_algorithm(&self) -> HashAlgorithm {
+        self.hash_algorithm
     }
 
     // Closes the channel, returning the commitments to be used in the final StarkProof
@@ -79,18 +296,22 @@ impl Channel {
 
 impl Default for Channel {
     fn default() -> Self {
-        Self::new()
+        Self::new_empty()
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use blake3::hash;
+
     use super::*;
 
+    use crate::{prover::generate_proof, StarkProof};
+
     // Get a few random elements and make sure they're different
     #[test]
     pub fn test_random_element() {
-        let mut channel = Channel::new();
+        let mut channel = Channel::new_empty();
 
         let r1 = channel.random_element();
         let r2 = channel.random_element();
@@ -99,4 +320,205 @@ mod tests {
         assert_ne!(r1, r2);
         assert_ne!(r2, r3);
     }
+
+    #[test]
+    pub fn test_different_pub_inputs() {
+        let mut channel_1 = Channel::new(&[1u8]);
+        let mut channel_2 = Channel::new(&[2u8]);
+
+        let r1: Vec<BaseField> = (0..4).map(|_| channel_1.random_element()).collect();
+        let r2: Vec<BaseField> = (0..4).map(|_| channel_2.random_element()).collect();
+
+        assert_ne!(r1, r2);
+    }
+
+    #[test]
+    pub fn test_different_salts() {
+        let mut channel_1 = Channel::with_salt(&[1u8]);
+        let mut channel_2 = Channel::with_salt(&[2u8]);
+
+        let r1: Vec<BaseField> = (0..4).map(|_| channel_1.random_element()).collect();
+        let r2: Vec<BaseField> = (0..4).map(|_| channel_2.random_element()).collect();
+
+        assert_ne!(r1, r2);
+
+        // The default salt
+        assert_eq!(
+            Channel::with_salt(&CHANNEL_SALT).random_element(),
+            Channel::new_empty().random_element()
+        );
+    }
+
+    #[test]
+    pub fn test_incorporate_nonce() {
+        let mut channel_1 = Channel::new_empty();
+        let mut channel_2 = Channel::new_empty();
+
+        channel_1.incorporate_nonce(0);
+        channel_2.incorporate_nonce(1);
+
+        assert_ne!(channel_1.current_hash, channel_2.current_hash);
+        assert!(channel_1.finalize().is_empty());
+    }
+
+    #[test]
+    pub fn test_fork() {
+        let mut channel = Channel::new_empty();
+        channel.random_element();
+
+        let mut fork_a = channel.fork(b"A");
+        let mut fork_b = channel.fork(b"B");
+
+        let r_a: Vec<BaseField> = (0..4).map(|_| fork_a.random_element()).collect();
+        let r_b: Vec<BaseField> = (0..4).map(|_| fork_b.random_element()).collect();
+        assert_ne!(r_a, r_b);
+
+        // Forking doesn't affect the parent
+        let r_parent: Vec<BaseField> = (0..4).map(|_| channel.random_element()).collect();
+        assert_ne!(r_parent, r_a);
+        assert_ne!(r_parent, r_b);
+    }
+
+    #[test]
+    pub fn test_draw_distinct_nonzero_elements() {
+        let mut channel = Channel::new_empty();
+
+        let elements = channel.draw_distinct_nonzero_elements(8);
+        assert_eq!(elements.len(), 8);
+
+        for (i, element) in elements.iter().enumerate() {
+            assert_ne!(*element, BaseField::zero());
+            assert!(!elements[i + 1..].contains(element));
+        }
+
+        // Every nonzero element
+        assert_eq!(channel.draw_distinct_nonzero_elements(16).len(), 16);
+    }
+
+    #[test]
+    pub fn test_transcript_hash() {
+        let transcript = |proof: &StarkProof| {
+            let mut channel = Channel::new(&[]);
+            channel.commit(proof.trace_lde_commitment);
+            channel.commit(proof.composition_poly_lde_commitment);
+            channel.commit(proof.fri.commitment.layer_roots[0]);
+
+            channel.transcript_hash()
+        };
+
+        let proof_1 = generate_proof(&[]);
+        let proof_2 = generate_proof(&[]);
+        assert_eq!(transcript(&proof_1), transcript(&proof_2));
+
+        let mut tampered_proof = proof_1.clone();
+        tampered_proof.composition_poly_lde_commitment = hash(b"tampered");
+        assert_ne!(transcript(&proof_1), transcript(&tampered_proof));
+    }
+
+    #[test]
+    pub fn test_transcript() {
+        assert!(Channel::new_empty().transcript().is_none());
+
+        let mut channel = Channel::new_recording(&[]);
+        channel.commit(hash(b"commitment"));
+        let element = channel.random_element();
+        let integer = channel.random_u64(6);
+        let bytes = channel.random_bytes(4);
+
+        let transcript = channel.transcript().unwrap().clone();
+        assert_eq!(
+            transcript.messages,
+            vec![
+                TranscriptMessage::Commit(hash(b"commitment")),
+                TranscriptMessage::Challenge(element),
+                TranscriptMessage::Integer(integer),
+                TranscriptMessage::Bytes(bytes),
+            ]
+        );
+        assert_eq!(transcript.first_divergence(&transcript), None);
+
+        // A channel that commits something else diverges right away
+        let mut other_channel = Channel::new_recording(&[]);
+        other_channel.commit(hash(b"other commitment"));
+        let other_transcript = other_channel.transcript().unwrap();
+        assert_eq!(transcript.first_divergence(other_transcript), Some(0));
+
+        // A prefix diverges where it ends
+        let prefix = ChannelTranscript {
+            messages: transcript.messages[..2].to_vec(),
+        };
+        assert_eq!(transcript.first_divergence(&prefix), Some(2));
+
+        // A channel that draws a different number of bytes diverges there
+        let mut other_channel = Channel::new_recording(&[]);
+        other_channel.commit(hash(b"commitment"));
+        other_channel.random_element();
+        other_channel.random_u64(6);
+        other_channel.random_bytes(5);
+        let other_transcript = other_channel.transcript().unwrap();
+        assert_eq!(transcript.first_divergence(other_transcript), Some(3));
+    }
+
+    #[test]
+    pub fn test_grind() {
+        let mut prover_channel = Channel::new_empty();
+        let nonce = prover_channel.grind(8);
+
+        let mut verifier_channel = Channel::new_empty();
+        assert!(verifier_channel.verify_grind(nonce, 8));
+        assert_eq!(
+            prover_channel.random_element(),
+            verifier_channel.random_element()
+        );
+
+        // `grind()` returns the smallest valid nonce
+        let channel = Channel::new_empty();
+        assert!((0..nonce).all(|smaller_nonce| !channel.is_valid_grind_nonce(smaller_nonce, 8)));
+        assert!(Channel::new_empty().verify_grind(nonce, 0));
+    }
+
+    #[test]
+    pub fn test_random_u64_uniform() {
+        const UPPER_BOUND: u64 = 1_000_000;
+        const NUM_DRAWS: usize = 1000;
+        const NUM_BUCKETS: usize = 10;
+
+        let mut channel = Channel::new_empty();
+
+        let mut buckets = [0usize; NUM_BUCKETS];
+        for _ in 0..NUM_DRAWS {
+            let value = channel.random_u64(UPPER_BOUND);
+            assert!(value < UPPER_BOUND);
+
+            buckets[(value * NUM_BUCKETS as u64 / UPPER_BOUND) as usize] += 1;
+        }
+
+        // Chi-squared test with 9 degrees of freedom: the critical value at
+        // the 0.001 significance level is 27.88
+        let expected = (NUM_DRAWS / NUM_BUCKETS) as f64;
+        let chi_squared: f64 = buckets
+            .iter()
+            .map(|count| (*count as f64 - expected).powi(2) / expected)
+            .sum();
+        assert!(chi_squared < 27.88, "chi-squared: {chi_squared}");
+    }
+
+    #[test]
+    pub fn test_random_bytes() {
+        let mut channel = Channel::new_empty();
+        let bytes_1 = channel.random_bytes(64);
+        let bytes_2 = channel.random_bytes(64);
+        assert_eq!(bytes_1.len(), 64);
+        assert_ne!(bytes_1, bytes_2);
+
+        // Two 32-byte draws give the same bytes as one 64-byte draw
+        let mut channel = Channel::new_empty();
+        let concatenated = [channel.random_bytes(32), channel.random_bytes(32)].concat();
+        assert_eq!(concatenated, bytes_1);
+
+        // Lengths that are not a multiple of 32 are truncated
+        let mut channel = Channel::new_empty();
+        assert_eq!(channel.random_bytes(40), bytes_1[0..40]);
+        assert!(channel.random_bytes(0).is_empty());
+    }
 }
@@ -1,29 +1,786 @@
-use crate::{field::BaseField, poly::Polynomial};
+use anyhow::bail;
+
+use crate::{
+    channel::Channel,
+    domain::DOMAIN_TRACE,
+    field::BaseField,
+    poly::Polynomial,
+    trace::{squaring_trace, Trace},
+};
 
 /// Polynomial representation of our boundary constraint that the first element
-/// of the trace is 3; that is, t(1) = 3. This gets converted into a statement
-/// of the form "<some expression agreed to by the prover and verifier> is a
-/// polynomial". We prove that by constructing the polynomial, and proving that
-/// we have it using FRI.
-///
-/// Note that we were able to derive the polynomial by hand because this library
-/// only cares about this problem (i.e. this boundary constraint). In a more
-/// general system like winterfell, we would need to programatically derive the
-/// polynomial.
-fn boundary_constraint() -> Polynomial {
-    Polynomial::new(vec![14.into(), 15.into(), 13.into()])
+/// of the trace is `first_element`; that is, t(trace_domain[0]) =
+/// first_element. This gets converted into a statement of the form "<some
+/// expression agreed to by the prover and verifier> is a polynomial". We prove
+/// that by constructing the polynomial, and proving that we have it using FRI.
+///
+/// We derive the polynomial by interpolating the trace `t` (which is fully
+/// determined by its first element), and computing
+///
+///   (t(x) - first_element) / (x - trace_domain[0])
+///
+/// For `first_element = 3`, this is `13x^2 + 15x + 14`.
+pub fn boundary_constraint(first_element: BaseField, trace_domain: &[BaseField]) -> Polynomial {
+    let trace = squaring_trace(first_element, trace_domain.len());
+
+    boundary_quotient(&trace, first_element, trace_domain)
+        .expect("t(x) - first_element has a root at the first domain element")
 }
 
 /// This polynomial encodes the transition constraints that check that for the
-/// first 3 elements `x` of the trace, the next is equal to `x^2`.
-fn transition_constraint() -> Polynomial {
-    Polynomial::new(vec![16.into(), 9.into(), 12.into(), 1.into()])
+/// first 3 elements `x` of the trace, the next is equal to `x^2`. Like
+/// `boundary_constraint()`, it is derived from the trace that starts with
+/// `first_element` (see `transition_quotient()`).
+///
+/// For `first_element = 3`, this is `x^3 + 12x^2 + 9x + 16`.
+fn transition_constraint(first_element: BaseField, trace_domain: &[BaseField]) -> Polynomial {
+    let trace = squaring_trace(first_element, trace_domain.len());
+
+    transition_quotient(&trace, trace_domain)
+        .expect("every element is the square of the previous one")
+}
+
+/// Computes the transition constraint for a trace where each element is the
+/// cube of the previous one; that is, `(t(gx) - t(x)^3) / ((x -
+/// trace_domain[0]) ... (x - trace_domain[n-2]))`, where `t` is `trace_poly`
+/// and `g` is `domain_gen`.
+///
+/// Because of the cubic term, the numerator has degree `3 * deg(t)` (as
+/// opposed to `2 * deg(t)` for the squaring constraint), and so the quotient
+/// has degree `3 * deg(t) - (n - 1)`. See
+/// `transition_constraint_quotient_degree()`.
+///
+/// Panics if the division is not exact (i.e. if an element of the trace is not
+/// the cube of the previous one).
+pub fn cubic_transition_constraint(
+    trace_poly: &Polynomial,
+    domain_gen: BaseField,
+    trace_domain: &[BaseField],
+) -> Polynomial {
+    let numerator = trace_poly.scale_x(domain_gen)
+        - trace_poly.clone() * trace_poly.clone() * trace_poly.clone();
+    let denominator = Polynomial::vanishing_polynomial(&trace_domain[0..trace_domain.len() - 1]);
+
+    numerator
+        .div_exact(&denominator)
+        .unwrap_or_else(|err| panic!("trace is not a cubing trace: {err}"))
+}
+
+/// Computes `(t(x) - first_element) / (x - trace_domain[0])`, where `t` is the
+/// polynomial interpolating `trace` over `trace_domain`. Errors if the division
+/// is not exact (i.e. if the trace doesn't start with `first_element`).
+fn boundary_quotient(
+    trace: &[BaseField],
+    first_element: BaseField,
+    trace_domain: &[BaseField],
+) -> anyhow::Result<Polynomial> {
+    let trace_polynomial = Polynomial::lagrange_interp(trace_domain, trace)?;
+
+    let numerator = trace_polynomial - Polynomial::new(vec![first_element]);
+    let denominator = Polynomial::vanishing_polynomial(&trace_domain[0..1]);
+
+    numerator.div_exact(&denominator)
+}
+
+/// Computes `(t(gx) - t(x)^2) / ((x - trace_domain[0]) ... (x -
+/// trace_domain[n-2]))`, where `t` is the polynomial interpolating `trace`
+/// over `trace_domain`, and `g` is the generator of `trace_domain`. Errors if
+/// the division is not exact (i.e. if an element of the trace is not the
+/// square of the previous one).
+fn transition_quotient(
+    trace: &[BaseField],
+    trace_domain: &[BaseField],
+) -> anyhow::Result<Polynomial> {
+    let trace_polynomial = Polynomial::lagrange_interp(trace_domain, trace)?;
+    let generator = trace_domain[1];
+
+    let numerator =
+        trace_polynomial.scale_x(generator) - trace_polynomial.clone() * trace_polynomial;
+    let denominator = Polynomial::vanishing_polynomial(&trace_domain[0..trace_domain.len() - 1]);
+
+    numerator.div_exact(&denominator)
 }
 
 /// Note that we construct our composition polynomial as they do in Stark 101
 /// (i.e. by taking a random linear combination of the boundary and transition
 /// constraint polynomials) as opposed to what they do in
 /// [the lambdaclass blog post](https://blog.lambdaclass.com/diving-deep-fri#the-constraint-composition-polynomial)
-pub fn composition_polynomial(alpha_0: BaseField, alpha_1: BaseField) -> Polynomial {
-    boundary_constraint() * alpha_0 + transition_constraint() * alpha_1
+pub fn composition_polynomial(
+    first_element: BaseField,
+    alpha_0: BaseField,
+    alpha_1: BaseField,
+) -> Polynomial {
+    multi_alpha_combination(
+        &[
+            boundary_constraint(first_element, &DOMAIN_TRACE),
+            transition_constraint(first_element, &DOMAIN_TRACE),
+        ],
+        &[alpha_0, alpha_1],
+    )
+    .expect("as many constraints as alphas")
+}
+
+/// Computes the random linear combination `sum_i alphas[i] * polys[i]`.
+pub fn multi_alpha_combination(
+    polys: &[Polynomial],
+    alphas: &[BaseField],
+) -> anyhow::Result<Polynomial> {
+    if polys.len() != alphas.len() {
+        bail!(
+            "got {} polynomials, but {} alphas",
+            polys.len(),
+            alphas.len()
+        );
+    }
+
+    let combination = polys
+        .iter()
+        .zip(alphas.iter())
+        .map(|(poly, alpha)| poly.clone() * *alpha)
+        .sum();
+
+    Ok(combination)
+}
+
+/// Evaluates the boundary constraint polynomial at `x`, given the value of the
+/// trace polynomial at `x`. That is, computes
+///
+///   (trace(x) - trace_first_element) / (x - first_domain_point)
+///
+/// which is a polynomial only if `trace(first_domain_point) = trace_first_element`.
+///
+/// Precondition: `x != first_domain_point`
+pub fn check_boundary_constraint(
+    x: BaseField,
+    trace_x: BaseField,
+    trace_first_element: BaseField,
+    first_domain_point: BaseField,
+) -> BaseField {
+    let p1_x = trace_x - trace_first_element;
+
+    p1_x / (x - first_domain_point)
+}
+
+/// Computes `composition_polynomial(first_element, alpha_0, alpha_1).eval(x)`
+/// using only the values of the trace polynomial at `x` and `gx` (where `g` is
+/// the generator of the trace domain). This is how the verifier evaluates the
+/// composition polynomial, since it doesn't have access to the trace
+/// polynomial.
+///
+/// Precondition: `x` is not in `DOMAIN_TRACE`
+pub fn composition_poly_at_x(
+    x: BaseField,
+    trace_x: BaseField,
+    trace_gx: BaseField,
+    first_element: BaseField,
+    alpha_0: BaseField,
+    alpha_1: BaseField,
+) -> BaseField {
+    let boundary_constraint_x =
+        check_boundary_constraint(x, trace_x, first_element, DOMAIN_TRACE[0]);
+
+    let transition_constraint_x: BaseField = {
+        let p2_x = trace_gx - trace_x.exp(2);
+
+        let denom = (x - DOMAIN_TRACE[0]) * (x - DOMAIN_TRACE[1]) * (x - DOMAIN_TRACE[2]);
+
+        p2_x / denom
+    };
+
+    boundary_constraint_x * alpha_0 + transition_constraint_x * alpha_1
+}
+
+/// Returns `cp(-x)`, which the FRI protocol needs along with `cp(x)` to fold
+/// the composition polynomial `cp` (see `Polynomial::fri_step()`).
+pub fn composition_poly_at_neg_x(cp: &Polynomial, x: BaseField) -> BaseField {
+    cp.eval(-x)
+}
+
+/// Divides `constraint_poly` by the polynomial that vanishes on every
+/// `period`-th element of `trace_domain` (i.e. `trace_domain[0]`,
+/// `trace_domain[period]`, ...), excluding the last element, which has no next
+/// step. This is how we express a transition constraint that only needs to
+/// hold on every `period`-th step of the trace.
+///
+/// With `period = 1`, and `constraint_poly = t(gx) - t(x)^2`, this is the
+/// transition constraint of our problem.
+///
+/// Errors if `constraint_poly` doesn't vanish on every such element.
+pub fn periodic_constraint(
+    constraint_poly: Polynomial,
+    period: usize,
+    trace_domain: &[BaseField],
+) -> anyhow::Result<Polynomial> {
+    if period == 0 {
+        bail!("period must be at least 1");
+    }
+
+    let constrained_elements: Vec<BaseField> = trace_domain[..trace_domain.len().saturating_sub(1)]
+        .iter()
+        .step_by(period)
+        .copied()
+        .collect();
+
+    constraint_poly.div_exact(&Polynomial::vanishing_polynomial(&constrained_elements))
+}
+
+/// Returns the degree of a random linear combination `sum_i alpha_i * p_i(x)`
+/// of constraint polynomials with the given degrees. With overwhelming
+/// probability over the choice of the `alpha_i`, the leading terms don't cancel
+/// out, and this is simply the maximum degree.
+///
+/// This is why, in general, all constraints should first be normalized to the
+/// same degree before being combined (e.g. by multiplying each by `x^k` for an
+/// appropriate `k`). Otherwise, a malicious prover could cheat on a
+/// low-degree constraint, and the excess degree would be hidden by the
+/// higher-degree constraints in the combination; FRI would only ever check the
+/// maximum degree.
+pub fn alpha_combination_degree(degrees: &[usize]) -> usize {
+    degrees.iter().copied().max().unwrap_or(0)
+}
+
+/// Returns the degree of the boundary constraint quotient `p(x) / v(x)`, where
+/// `p` has degree `boundary_poly_degree`, and the vanishing polynomial `v` has
+/// degree `vanishing_poly_degree` (i.e. the number of constrained elements).
+///
+/// Panics if `v` has a higher degree than `p`, since `v` can't divide `p`.
+pub fn boundary_constraint_quotient_degree(
+    boundary_poly_degree: usize,
+    vanishing_poly_degree: usize,
+) -> usize {
+    boundary_poly_degree
+        .checked_sub(vanishing_poly_degree)
+        .expect("vanishing polynomial has a higher degree than the boundary polynomial")
+}
+
+/// Same as `boundary_constraint_quotient_degree()`, but for the transition
+/// constraint. For the squaring constraint `t(gx) - t(x)^2`, the numerator has
+/// twice the degree of the trace polynomial.
+pub fn transition_constraint_quotient_degree(
+    transition_poly_degree: usize,
+    vanishing_poly_degree: usize,
+) -> usize {
+    transition_poly_degree
+        .checked_sub(vanishing_poly_degree)
+        .expect("vanishing polynomial has a higher degree than the transition polynomial")
+}
+
+/// Checks that `cp` is consistent with being a combination of constraint
+/// quotients, rather than a rational function. Every constraint quotient has
+/// degree less than `trace_domain.len()` when its denominator divides the
+/// numerator cleanly. If it doesn't (i.e. the trace violates a constraint), a
+/// prover that evaluates the rational function over the LDE domain and
+/// interpolates the result gets a polynomial of much higher degree. This is
+/// exactly what FRI detects, except that here we look at all the coefficients.
+pub fn verify_composition_poly_is_polynomial(cp: &Polynomial, trace_domain: &[BaseField]) -> bool {
+    cp.clone().trimmed().degree() < trace_domain.len()
+}
+
+/// A constraint that the trace must satisfy, expressed as a polynomial derived
+/// from the trace.
+pub trait Constraint {
+    /// Returns the constraint polynomial for `trace`.
+    ///
+    /// Panics if `trace` doesn't satisfy the constraint, since the constraint
+    /// is then not a polynomial.
+    fn apply_to_trace(&self, trace: &Trace) -> Polynomial;
+
+    /// Evaluates the constraint on row `row` of the trace. This is 0 if the
+    /// row satisfies the constraint (or if the constraint doesn't apply to
+    /// that row).
+    fn evaluate_at_row(&self, trace: &Trace, row: usize) -> BaseField;
+
+    /// The maximum degree of the constraint polynomial
+    fn degree_bound(&self) -> usize;
+
+    /// Name of the constraint, used in error messages
+    fn name(&self) -> &str;
+}
+
+/// The constraint that the first element of column `column` of the trace is
+/// `first_element`. See `boundary_constraint()`.
+pub struct BoundaryConstraint {
+    pub column: usize,
+    pub first_element: BaseField,
+}
+
+impl Constraint for BoundaryConstraint {
+    fn apply_to_trace(&self, trace: &Trace) -> Polynomial {
+        boundary_quotient(trace.column(self.column), self.first_element, &DOMAIN_TRACE)
+            .unwrap_or_else(|err| panic!("trace violates the {} constraint: {err}", self.name()))
+    }
+
+    fn evaluate_at_row(&self, trace: &Trace, row: usize) -> BaseField {
+        if row == 0 {
+            trace.column(self.column)[0] - self.first_element
+        } else {
+            BaseField::zero()
+        }
+    }
+
+    fn degree_bound(&self) -> usize {
+        DOMAIN_TRACE.len() - 1
+    }
+
+    fn name(&self) -> &str {
+        "boundary"
+    }
+}
+
+/// The constraint that every element of column `column` of the trace is the
+/// square of the previous one. See `transition_constraint()`.
+pub struct TransitionConstraint {
+    pub column: usize,
+}
+
+impl Constraint for TransitionConstraint {
+    fn apply_to_trace(&self, trace: &Trace) -> Polynomial {
+        transition_quotient(trace.column(self.column), &DOMAIN_TRACE)
+            .unwrap_or_else(|err| panic!("trace violates the {} constraint: {err}", self.name()))
+    }
+
+    fn evaluate_at_row(&self, trace: &Trace, row: usize) -> BaseField {
+        let column = trace.column(self.column);
+
+        // The last row has no next row
+        if row + 1 < column.len() {
+            column[row + 1] - column[row].square()
+        } else {
+            BaseField::zero()
+        }
+    }
+
+    fn degree_bound(&self) -> usize {
+        // deg(t(x)^2) - deg(denominator) = 2 * (n - 1) - (n - 1)
+        DOMAIN_TRACE.len() - 1
+    }
+
+    fn name(&self) -> &str {
+        "transition"
+    }
+}
+
+/// A set of constraints, combined into the composition polynomial using a
+/// random linear combination.
+#[derive(Default)]
+pub struct ConstraintSystem {
+    constraints: Vec<Box<dyn Constraint>>,
+}
+
+impl ConstraintSystem {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, c: impl Constraint + 'static) {
+        self.constraints.push(Box::new(c));
+    }
+
+    /// Draws one alpha per constraint from the channel (in the order the
+    /// constraints were added), and returns the random linear combination of
+    /// the constraint polynomials for `trace`.
+    pub fn compose_random(&self, trace: &Trace, channel: &mut Channel) -> Polynomial {
+        let alphas: Vec<BaseField> = self
+            .constraints
+            .iter()
+            .map(|_| channel.random_element())
+            .collect();
+
+        let polys: Vec<Polynomial> = self
+            .constraints
+            .iter()
+            .map(|constraint| constraint.apply_to_trace(trace))
+            .collect();
+
+        multi_alpha_combination(&polys, &alphas).expect("as many constraints as alphas")
+    }
+
+    /// Checks that every row of `trace` satisfies every constraint. Errors with
+    /// the name of the first violated constraint.
+    pub fn check_trace(&self, trace: &Trace) -> anyhow::Result<()> {
+        for constraint in self.constraints.iter() {
+            for row in 0..trace.len() {
+                let value = constraint.evaluate_at_row(trace, row);
+
+                if value != BaseField::zero() {
+                    bail!(
+                        "trace violates the {} constraint at row {row} (evaluates to {value})",
+                        constraint.name()
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        domain::DOMAIN_LDE,
+        poly::{check_polynomial_identity_over_domain, vanishes_on_domain_fast},
+        trace::{generate_power_trace, generate_trace, TRACE_FIRST_ELEMENT},
+    };
+
+    #[test]
+    pub fn boundary_constraint_zero_when_trace_matches() {
+        // The constraint can't be evaluated at the first trace domain point
+        // itself, since its denominator vanishes there. Instead, we check the
+        // equivalent statement at every point of the LDE domain: if the trace
+        // takes the value of the first element, the numerator (and hence the
+        // constraint) vanishes.
+        for x in &DOMAIN_LDE {
+            let boundary_constraint_x = check_boundary_constraint(
+                *x,
+                TRACE_FIRST_ELEMENT,
+                TRACE_FIRST_ELEMENT,
+                DOMAIN_TRACE[0],
+            );
+
+            assert_eq!(boundary_constraint_x, BaseField::zero());
+        }
+    }
+
+    #[test]
+    pub fn composition_polynomial_matches_manual_computation() {
+        let alpha_0 = BaseField::from(5);
+        let alpha_1 = BaseField::from(11);
+
+        let cp = composition_polynomial(TRACE_FIRST_ELEMENT, alpha_0, alpha_1);
+
+        // alpha_0 * (14 + 15x + 13x^2) + alpha_1 * (16 + 9x + 12x^2 + x^3)
+        let manual_cp = Polynomial::new(vec![
+            alpha_0 * 14.into() + alpha_1 * 16.into(),
+            alpha_0 * 15.into() + alpha_1 * 9.into(),
+            alpha_0 * 13.into() + alpha_1 * 12.into(),
+            alpha_1,
+        ]);
+
+        check_polynomial_identity_over_domain(&cp, &manual_cp, &DOMAIN_LDE).unwrap();
+    }
+
+    #[test]
+    pub fn composition_polynomial_for_every_first_element() {
+        let (alpha_0, alpha_1) = (BaseField::from(5), BaseField::from(11));
+        let generator = DOMAIN_TRACE[1];
+
+        // The composition polynomial is the one the verifier evaluates from the
+        // trace that starts with `first_element`
+        for first_element in (1..17u8).map(BaseField::from) {
+            let trace = squaring_trace(first_element, DOMAIN_TRACE.len());
+            let trace_poly = Polynomial::lagrange_interp(&DOMAIN_TRACE, &trace).unwrap();
+            let cp = composition_polynomial(first_element, alpha_0, alpha_1);
+
+            for x in &DOMAIN_LDE {
+                assert_eq!(
+                    cp.eval(*x),
+                    composition_poly_at_x(
+                        *x,
+                        trace_poly.eval(*x),
+                        trace_poly.eval(generator * *x),
+                        first_element,
+                        alpha_0,
+                        alpha_1
+                    ),
+                    "first element {first_element}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    pub fn constraints_eval_table() {
+        let constraints = [
+            boundary_constraint(TRACE_FIRST_ELEMENT, &DOMAIN_TRACE),
+            transition_constraint(TRACE_FIRST_ELEMENT, &DOMAIN_TRACE),
+        ];
+
+        let table = Polynomial::eval_table(&constraints, &DOMAIN_LDE);
+
+        assert_eq!(table.len(), constraints.len());
+        for (constraint, row) in constraints.iter().zip(table.iter()) {
+            assert_eq!(row.len(), DOMAIN_LDE.len());
+
+            for (x, value) in DOMAIN_LDE.iter().zip(row.iter()) {
+                assert!(value.as_byte() < 17);
+                assert_eq!(*value, constraint.eval(*x));
+            }
+        }
+    }
+
+    #[test]
+    pub fn alpha_combination_degree_is_max() {
+        let degrees = [
+            boundary_constraint(TRACE_FIRST_ELEMENT, &DOMAIN_TRACE).degree(),
+            transition_constraint(TRACE_FIRST_ELEMENT, &DOMAIN_TRACE).degree(),
+        ];
+
+        assert_eq!(alpha_combination_degree(&degrees), 3);
+        assert_eq!(
+            alpha_combination_degree(&degrees),
+            composition_polynomial(TRACE_FIRST_ELEMENT, 3.into(), 7.into()).degree()
+        );
+        assert_eq!(alpha_combination_degree(&[]), 0);
+    }
+
+    #[test]
+    pub fn composition_poly_at_x_matches_eval() {
+        let trace_polynomial =
+            Polynomial::lagrange_interp(&DOMAIN_TRACE, generate_trace().column(0)).unwrap();
+        let alpha_0 = BaseField::from(5);
+        let alpha_1 = BaseField::from(11);
+
+        let cp = composition_polynomial(TRACE_FIRST_ELEMENT, alpha_0, alpha_1);
+
+        for x in &DOMAIN_LDE {
+            let trace_x = trace_polynomial.eval(*x);
+            let trace_gx = trace_polynomial.eval(DOMAIN_TRACE[1] * *x);
+
+            assert_eq!(
+                composition_poly_at_x(*x, trace_x, trace_gx, TRACE_FIRST_ELEMENT, alpha_0, alpha_1),
+                cp.eval(*x)
+            );
+        }
+    }
+
+    #[test]
+    pub fn composition_poly_at_neg_x_matches_neg_x() {
+        let cp = composition_polynomial(TRACE_FIRST_ELEMENT, 5.into(), 11.into());
+
+        for x in &DOMAIN_LDE {
+            assert_eq!(composition_poly_at_neg_x(&cp, *x), cp.neg_x().eval(*x));
+        }
+    }
+
+    #[test]
+    pub fn multi_alpha_combination_3_constraints() {
+        let polys = [
+            Polynomial::new(vec![1.into(), 2.into()]),
+            Polynomial::new(vec![3.into(), 4.into(), 5.into()]),
+            Polynomial::new(vec![6.into()]),
+        ];
+        let alphas: [BaseField; 3] = [2.into(), 3.into(), 4.into()];
+
+        let combination = multi_alpha_combination(&polys, &alphas).unwrap();
+
+        // 2 * (1 + 2x) + 3 * (3 + 4x + 5x^2) + 4 * 6
+        assert_eq!(
+            combination,
+            Polynomial::new(vec![35.into(), 16.into(), 15.into()])
+        );
+
+        assert!(multi_alpha_combination(&polys, &alphas[0..2]).is_err());
+    }
+
+    /// Returns `t(gx) - t(x)^2`, where `t` is the trace polynomial, and `g`
+    /// the generator of the trace domain.
+    fn transition_constraint_numerator() -> Polynomial {
+        let trace_polynomial =
+            Polynomial::lagrange_interp(&DOMAIN_TRACE, generate_trace().column(0)).unwrap();
+
+        let trace_polynomial_gx = trace_polynomial.scale_x(DOMAIN_TRACE[1]);
+
+        trace_polynomial_gx - trace_polynomial.clone() * trace_polynomial
+    }
+
+    #[test]
+    pub fn periodic_constraint_period_1() {
+        let constraint =
+            periodic_constraint(transition_constraint_numerator(), 1, &DOMAIN_TRACE).unwrap();
+
+        assert_eq!(
+            constraint,
+            transition_constraint(TRACE_FIRST_ELEMENT, &DOMAIN_TRACE)
+        );
+    }
+
+    #[test]
+    pub fn periodic_constraint_period_2() {
+        let numerator = transition_constraint_numerator();
+
+        let constraint = periodic_constraint(numerator.clone(), 2, &DOMAIN_TRACE).unwrap();
+
+        // Only the roots at DOMAIN_TRACE[0] and DOMAIN_TRACE[2] were divided out
+        assert_eq!(
+            constraint.degree(),
+            numerator.clone().trimmed().degree() - 2
+        );
+        assert_eq!(
+            constraint * Polynomial::vanishing_polynomial(&[DOMAIN_TRACE[0], DOMAIN_TRACE[2]]),
+            numerator.trimmed()
+        );
+
+        // A polynomial that doesn't vanish on the trace domain is rejected
+        assert!(periodic_constraint(Polynomial::one(), 2, &DOMAIN_TRACE).is_err());
+        assert!(periodic_constraint(Polynomial::one(), 0, &DOMAIN_TRACE).is_err());
+    }
+
+    #[test]
+    pub fn composition_poly_is_polynomial() {
+        let cp = composition_polynomial(TRACE_FIRST_ELEMENT, 5.into(), 11.into());
+        assert!(verify_composition_poly_is_polynomial(&cp, &DOMAIN_TRACE));
+
+        // Claim that the trace starts with 4 instead of 3: the boundary
+        // "quotient" `(t(x) - 4) / (x - 1)` is now a rational function. We
+        // evaluate it over the LDE domain, and interpolate the result.
+        let trace_polynomial =
+            Polynomial::lagrange_interp(&DOMAIN_TRACE, generate_trace().column(0)).unwrap();
+        let broken_evaluations: Vec<BaseField> = DOMAIN_LDE
+            .iter()
+            .map(|x| (trace_polynomial.eval(*x) - 4.into()) / (*x - DOMAIN_TRACE[0]))
+            .collect();
+        let broken_cp = Polynomial::lagrange_interp(&DOMAIN_LDE, &broken_evaluations).unwrap();
+
+        assert!(!verify_composition_poly_is_polynomial(
+            &broken_cp,
+            &DOMAIN_TRACE
+        ));
+    }
+
+    #[test]
+    pub fn constraint_quotient_degrees() {
+        let trace_poly_degree = DOMAIN_TRACE.len() - 1;
+
+        // The boundary constraint is only enforced on the first element
+        let boundary_degree = boundary_constraint_quotient_degree(trace_poly_degree, 1);
+        assert_eq!(boundary_degree, 2);
+        assert_eq!(
+            boundary_degree,
+            boundary_constraint(TRACE_FIRST_ELEMENT, &DOMAIN_TRACE).degree()
+        );
+
+        // The transition constraint is enforced on all but the last element
+        let transition_degree =
+            transition_constraint_quotient_degree(2 * trace_poly_degree, DOMAIN_TRACE.len() - 1);
+        assert_eq!(transition_degree, 3);
+        assert_eq!(
+            transition_degree,
+            transition_constraint(TRACE_FIRST_ELEMENT, &DOMAIN_TRACE).degree()
+        );
+    }
+
+    #[test]
+    pub fn cubic_transition_constraint_valid_trace() {
+        let trace = generate_power_trace(TRACE_FIRST_ELEMENT, 3, DOMAIN_TRACE.len());
+        let trace_poly = Polynomial::lagrange_interp(&DOMAIN_TRACE, &trace).unwrap();
+
+        let constraint = cubic_transition_constraint(&trace_poly, DOMAIN_TRACE[1], &DOMAIN_TRACE);
+
+        // The numerator vanishes on every element of the trace domain but the
+        // last
+        let trace_domain: &[BaseField] = &DOMAIN_TRACE;
+        let constrained_elements = &trace_domain[0..trace_domain.len() - 1];
+        let numerator = trace_poly.scale_x(DOMAIN_TRACE[1])
+            - trace_poly.clone() * trace_poly.clone() * trace_poly.clone();
+        assert!(vanishes_on_domain_fast(&numerator, constrained_elements));
+        assert_eq!(
+            constraint.clone() * Polynomial::vanishing_polynomial(constrained_elements),
+            numerator.trimmed()
+        );
+
+        let trace_poly_degree = trace_poly.clone().trimmed().degree();
+        assert_eq!(
+            constraint.trimmed().degree(),
+            transition_constraint_quotient_degree(3 * trace_poly_degree, DOMAIN_TRACE.len() - 1)
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    pub fn cubic_transition_constraint_squaring_trace() {
+        let trace_poly =
+            Polynomial::lagrange_interp(&DOMAIN_TRACE, generate_trace().column(0)).unwrap();
+
+        cubic_transition_constraint(&trace_poly, DOMAIN_TRACE[1], &DOMAIN_TRACE);
+    }
+
+    #[test]
+    pub fn boundary_constraint_matches_hardcoded() {
+        assert_eq!(
+            boundary_constraint(BaseField::new(3), &DOMAIN_TRACE),
+            Polynomial::new(vec![14.into(), 15.into(), 13.into()])
+        );
+    }
+
+    #[test]
+    pub fn constraint_trait() {
+        let trace = generate_trace();
+
+        let boundary = BoundaryConstraint {
+            column: 0,
+            first_element: TRACE_FIRST_ELEMENT,
+        };
+        let boundary_poly = boundary.apply_to_trace(&trace);
+        assert_eq!(
+            boundary_poly,
+            boundary_constraint(TRACE_FIRST_ELEMENT, &DOMAIN_TRACE)
+        );
+        assert!(boundary_poly.degree() <= boundary.degree_bound());
+        assert_eq!(boundary.name(), "boundary");
+
+        let transition = TransitionConstraint { column: 0 };
+        let transition_poly = transition.apply_to_trace(&trace);
+        assert_eq!(
+            transition_poly,
+            transition_constraint(TRACE_FIRST_ELEMENT, &DOMAIN_TRACE)
+        );
+        assert!(transition_poly.degree() <= transition.degree_bound());
+        assert_eq!(transition.name(), "transition");
+    }
+
+    #[test]
+    #[should_panic]
+    pub fn constraint_trait_invalid_trace() {
+        let trace = Trace::new(vec![vec![3.into(), 9.into(), 14.into(), 16.into()]]).unwrap();
+
+        TransitionConstraint { column: 0 }.apply_to_trace(&trace);
+    }
+
+    fn squaring_constraint_system() -> ConstraintSystem {
+        let mut constraint_system = ConstraintSystem::new();
+        constraint_system.add(BoundaryConstraint {
+            column: 0,
+            first_element: TRACE_FIRST_ELEMENT,
+        });
+        constraint_system.add(TransitionConstraint { column: 0 });
+
+        constraint_system
+    }
+
+    #[test]
+    pub fn constraint_system_compose_random() {
+        let constraint_system = squaring_constraint_system();
+
+        let cp = constraint_system.compose_random(&generate_trace(), &mut Channel::new_empty());
+
+        let mut channel = Channel::new_empty();
+        let alpha_0 = channel.random_element();
+        let alpha_1 = channel.random_element();
+        assert_eq!(
+            cp,
+            composition_polynomial(TRACE_FIRST_ELEMENT, alpha_0, alpha_1)
+        );
+    }
+
+    #[test]
+    pub fn constraint_system_check_trace() {
+        let constraint_system = squaring_constraint_system();
+
+        assert!(constraint_system.check_trace(&generate_trace()).is_ok());
+
+        let bad_first_element =
+            Trace::new(vec![vec![4.into(), 16.into(), 1.into(), 1.into()]]).unwrap();
+        let err = constraint_system
+            .check_trace(&bad_first_element)
+            .unwrap_err();
+        assert!(err.to_string().contains("boundary"));
+
+        let bad_transition =
+            Trace::new(vec![vec![3.into(), 9.into(), 14.into(), 16.into()]]).unwrap();
+        let err = constraint_system.check_trace(&bad_transition).unwrap_err();
+        assert!(err.to_string().contains("transition"));
+    }
 }
@@ -1,5 +1,7 @@
 use std::ops::{Deref, Index};
 
+use anyhow::bail;
+
 use crate::field::BaseField;
 
 /// Represents the domain of the trace polynomial. That is, when we interpolate
@@ -67,6 +69,47 @@ impl<const N: usize, const GENERATOR: u8> Domain<N, GENERATOR> {
     pub const fn generator() -> BaseField {
         BaseField::new(GENERATOR)
     }
+
+    /// Returns the position of `element` in the domain, or `None` if it isn't
+    /// in the domain.
+    pub fn index_of(&self, element: BaseField) -> Option<usize> {
+        self.elements.iter().position(|ele| *ele == element)
+    }
+
+    /// Returns `true` if `element` is in the domain
+    pub fn contains(&self, element: BaseField) -> bool {
+        self.elements.contains(&element)
+    }
+
+    /// Prints every element of the domain, along with how it is obtained from
+    /// the generator. For example, for `DOMAIN_LDE`,
+    ///
+    /// DOMAIN_LDE[1] = 10 = 3 * gen^1
+    ///
+    /// where 3 is the coset shift (see `DOMAIN_LDE`).
+    pub fn print(&self, name: &str) {
+        print!("{}", self.describe(name));
+    }
+
+    fn describe(&self, name: &str) -> String {
+        let generator = Self::generator();
+        // The first element is always `shift * gen^0`
+        let shift = self.elements[0];
+
+        let mut out = format!("{name} (generator: {generator})\n");
+
+        for (i, ele) in self.elements.iter().enumerate() {
+            let exponent = BaseField::log(*ele / shift, generator);
+
+            if shift == BaseField::one() {
+                out.push_str(&format!("{name}[{i}] = {ele} = gen^{exponent}\n"));
+            } else {
+                out.push_str(&format!("{name}[{i}] = {ele} = {shift} * gen^{exponent}\n"));
+            }
+        }
+
+        out
+    }
 }
 
 impl<const N: usize, const GENERATOR: u8> Index<usize> for Domain<N, GENERATOR> {
@@ -84,3 +127,326 @@ impl<const N: usize, const GENERATOR: u8> Deref for Domain<N, GENERATOR> {
         &self.elements
     }
 }
+
+impl<'a, const N: usize, const GENERATOR: u8> IntoIterator for &'a Domain<N, GENERATOR> {
+    type Item = &'a BaseField;
+    type IntoIter = std::slice::Iter<'a, BaseField>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.elements.iter()
+    }
+}
+
+impl<const N: usize, const GENERATOR: u8> IntoIterator for Domain<N, GENERATOR> {
+    type Item = BaseField;
+    type IntoIter = std::array::IntoIter<BaseField, N>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.elements.into_iter()
+    }
+}
+
+/// A domain whose size and generator are only known at runtime (as opposed to
+/// `Domain`), such as the domains of the FRI layers. Like `Domain`, it is
+/// either a cyclic group or a coset of one.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DynamicDomain {
+    elements: Vec<BaseField>,
+    generator: BaseField,
+}
+
+impl DynamicDomain {
+    pub fn generator(&self) -> BaseField {
+        self.generator
+    }
+
+    /// Returns the position of `element` in the domain, or `None` if it isn't
+    /// in the domain.
+    pub fn index_of(&self, element: BaseField) -> Option<usize> {
+        self.elements.iter().position(|ele| *ele == element)
+    }
+
+    /// Returns `true` if `element` is in the domain
+    pub fn contains(&self, element: BaseField) -> bool {
+        self.elements.contains(&element)
+    }
+
+    /// Returns the domain of half the size obtained by squaring every element.
+    /// Squaring the first half or the second half gives the same domain, since
+    /// the second half is the negation of the first half; we square the first
+    /// half, to preserve the order of the elements. The generator is squared
+    /// as well.
+    pub fn halve(&self) -> Self {
+        let elements = self.elements[0..self.elements.len() / 2]
+            .iter()
+            .map(|x| x.exp(2))
+            .collect();
+
+        Self {
+            elements,
+            generator: self.generator.exp(2),
+        }
+    }
+}
+
+impl<const N: usize, const GENERATOR: u8> From<&Domain<N, GENERATOR>> for DynamicDomain {
+    fn from(domain: &Domain<N, GENERATOR>) -> Self {
+        Self {
+            elements: domain.elements.to_vec(),
+            generator: Domain::<N, GENERATOR>::generator(),
+        }
+    }
+}
+
+impl Index<usize> for DynamicDomain {
+    type Output = BaseField;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.elements[index]
+    }
+}
+
+impl Deref for DynamicDomain {
+    type Target = [BaseField];
+
+    fn deref(&self) -> &Self::Target {
+        &self.elements
+    }
+}
+
+impl<'a> IntoIterator for &'a DynamicDomain {
+    type Item = &'a BaseField;
+    type IntoIter = std::slice::Iter<'a, BaseField>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.elements.iter()
+    }
+}
+
+/// A multiplicative subgroup of `BaseField`, whose size is only known at
+/// runtime (as opposed to `Domain`). Elements are ordered by increasing power
+/// of the generator: `{1, g, g^2, ..., g^(size-1)}`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CyclicGroup {
+    generator: BaseField,
+    elements: Vec<BaseField>,
+}
+
+impl CyclicGroup {
+    /// Returns the subgroup of size `size`. Since 3 generates the whole
+    /// multiplicative group (of size 16), `3^(16/size)` generates the subgroup
+    /// of size `size`.
+    ///
+    /// Errors if `size` doesn't divide 16, in which case there is no such
+    /// subgroup.
+    pub fn new(size: usize) -> anyhow::Result<Self> {
+        let multiplicative_group_size = 16;
+        if size == 0 || multiplicative_group_size % size != 0 {
+            bail!("there is no subgroup of size {size}");
+        }
+
+        let generator = BaseField::new(3).exp((multiplicative_group_size / size) as u8);
+
+        Self::from_generator(generator, size)
+    }
+
+    /// Builds the group `{1, g, g^2, ..., g^(size-1)}`, where `g` is
+    /// `generator`.
+    ///
+    /// Errors if `generator` doesn't have order `size` (i.e. if the elements
+    /// are not all distinct, or if `g^size != 1`).
+    pub fn from_generator(generator: BaseField, size: usize) -> anyhow::Result<Self> {
+        let mut elements = Vec::with_capacity(size);
+        let mut element = BaseField::one();
+        for _ in 0..size {
+            elements.push(element);
+            element *= generator;
+        }
+
+        let has_distinct_elements = elements
+            .iter()
+            .enumerate()
+            .all(|(i, ele)| !elements[i + 1..].contains(ele));
+        if !has_distinct_elements || element != BaseField::one() {
+            bail!("{generator} doesn't have order {size}");
+        }
+
+        Ok(Self {
+            generator,
+            elements,
+        })
+    }
+
+    pub fn generator(&self) -> BaseField {
+        self.generator
+    }
+
+    pub fn elements(&self) -> &[BaseField] {
+        &self.elements
+    }
+
+    /// Returns `true` if `element` is in the group
+    pub fn contains(&self, element: BaseField) -> bool {
+        self.elements.contains(&element)
+    }
+
+    /// Iterates over the elements, in the same order as `elements()`
+    pub fn iter(&self) -> std::slice::Iter<'_, BaseField> {
+        self.elements.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.elements.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+}
+
+/// Returns `true` if `group` and `coset` share no element. See `DOMAIN_LDE` for
+/// why we want the LDE domain to be disjoint from the trace domain.
+pub fn check_coset_disjoint(group: &[BaseField], coset: &[BaseField]) -> bool {
+    find_coset_overlap(group, coset).is_empty()
+}
+
+/// Returns the elements that are in both `group` and `coset`.
+pub fn find_coset_overlap(group: &[BaseField], coset: &[BaseField]) -> Vec<BaseField> {
+    group
+        .iter()
+        .filter(|ele| coset.contains(ele))
+        .copied()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn lde_domain_disjoint_from_trace_domain() {
+        assert!(check_coset_disjoint(&DOMAIN_TRACE, &DOMAIN_LDE));
+        assert!(find_coset_overlap(&DOMAIN_TRACE, &DOMAIN_LDE).is_empty());
+    }
+
+    #[test]
+    pub fn unshifted_subgroup_overlaps_trace_domain() {
+        // The multiplicative subgroup of size 8, before we shift it by 3 (see
+        // `DOMAIN_LDE`)
+        let subgroup_size_8: Vec<BaseField> = (0..8).map(|i| BaseField::from(9).exp(i)).collect();
+
+        assert!(!check_coset_disjoint(&DOMAIN_TRACE, &subgroup_size_8));
+        assert_eq!(
+            find_coset_overlap(&DOMAIN_TRACE, &subgroup_size_8),
+            DOMAIN_TRACE.to_vec()
+        );
+    }
+
+    #[test]
+    pub fn domain_index_of() {
+        assert_eq!(DOMAIN_LDE.index_of(DOMAIN_LDE[3]), Some(3));
+        assert_eq!(DOMAIN_LDE.index_of(DOMAIN_TRACE[0]), None);
+
+        // `-x` is half-way around the domain from `x`
+        for (i, x) in DOMAIN_LDE.iter().enumerate() {
+            assert_eq!(DOMAIN_LDE.index_of(-*x), Some((i + 4) % 8));
+        }
+    }
+
+    #[test]
+    pub fn domain_into_iter() {
+        let by_ref: Vec<BaseField> = (&DOMAIN_TRACE).into_iter().copied().collect();
+        assert_eq!(by_ref, DOMAIN_TRACE.to_vec());
+
+        let domain: Domain<4, 13> = Domain {
+            elements: [1.into(), 13.into(), 16.into(), 4.into()],
+        };
+        let consumed: Vec<BaseField> = domain.into_iter().collect();
+        assert_eq!(consumed, DOMAIN_TRACE.to_vec());
+    }
+
+    #[test]
+    pub fn dynamic_domain_halve() {
+        let domain = DynamicDomain::from(&DOMAIN_LDE);
+        assert_eq!(domain.generator(), BaseField::from(9));
+        assert_eq!(domain.to_vec(), DOMAIN_LDE.to_vec());
+        assert_eq!(domain[1], DOMAIN_LDE[1]);
+
+        let halved = domain.halve();
+        assert_eq!(halved.len(), 4);
+        assert_eq!(halved.generator(), BaseField::from(13));
+        for (i, x) in halved.iter().enumerate() {
+            assert_eq!(*x, DOMAIN_LDE[i].exp(2));
+            assert!(halved.contains(*x));
+            assert_eq!(halved.index_of(*x), Some(i));
+        }
+
+        // Halving the trace domain (a group) gives a group
+        let halved = DynamicDomain::from(&DOMAIN_TRACE).halve();
+        assert_eq!(halved.to_vec(), vec![BaseField::one(), BaseField::from(16)]);
+        assert!(!halved.contains(BaseField::from(13)));
+    }
+
+    #[test]
+    pub fn domain_contains() {
+        assert!(DOMAIN_TRACE.contains(BaseField::new(1)));
+        assert!(!DOMAIN_TRACE.contains(BaseField::new(3)));
+
+        // The domains are disjoint
+        for x in &DOMAIN_LDE {
+            assert!(!DOMAIN_TRACE.contains(*x));
+            assert_eq!(DOMAIN_LDE.contains(*x), DOMAIN_LDE.index_of(*x).is_some());
+        }
+    }
+
+    #[test]
+    pub fn cyclic_group_from_generator() {
+        let group = CyclicGroup::from_generator(BaseField::new(13), 4).unwrap();
+        assert_eq!(group.elements(), DOMAIN_TRACE.to_vec());
+        assert_eq!(group.generator(), Domain::<4, 13>::generator());
+
+        assert_eq!(CyclicGroup::new(4).unwrap(), group);
+        assert_eq!(
+            CyclicGroup::new(8).unwrap().elements(),
+            CyclicGroup::from_generator(BaseField::new(9), 8)
+                .unwrap()
+                .elements()
+        );
+
+        // 13 has order 4
+        assert!(CyclicGroup::from_generator(BaseField::new(13), 8).is_err());
+        assert!(CyclicGroup::from_generator(BaseField::new(13), 3).is_err());
+        assert!(CyclicGroup::new(5).is_err());
+    }
+
+    #[test]
+    pub fn cyclic_group_contains() {
+        let group = CyclicGroup::new(4).unwrap();
+
+        assert!(group.contains(BaseField::new(1)));
+        assert!(!group.contains(BaseField::new(3)));
+
+        // The trace domain is disjoint from the LDE domain
+        for x in group.iter() {
+            assert!(DOMAIN_TRACE.contains(*x));
+            assert!(!DOMAIN_LDE.contains(*x));
+        }
+        assert_eq!(group.iter().count(), 4);
+    }
+
+    #[test]
+    pub fn describe_domains() {
+        let lde_description = DOMAIN_LDE.describe("DOMAIN_LDE");
+        assert!(lde_description.contains("DOMAIN_LDE[0] = 3 = 3 * gen^0"));
+        assert!(lde_description.contains("DOMAIN_LDE[1] = 10 = 3 * gen^1"));
+
+        assert_eq!(
+            DOMAIN_TRACE.describe("DOMAIN_TRACE"),
+            "DOMAIN_TRACE (generator: 13)\n\
+             DOMAIN_TRACE[0] = 1 = gen^0\n\
+             DOMAIN_TRACE[1] = 13 = gen^1\n\
+             DOMAIN_TRACE[2] = 16 = gen^2\n\
+             DOMAIN_TRACE[3] = 4 = gen^3\n"
+        );
+    }
+}
@@ -0,0 +1,166 @@
+use std::ops::{Add, Mul, Neg, Sub};
+
+use crate::{
+    field::BaseField,
+    poly::{poly_div, Polynomial},
+};
+
+/// Returns an irreducible polynomial of degree 2 over `BaseField`, which can be
+/// used to construct the extension field `GF(17^2)` (as polynomials modulo the
+/// irreducible polynomial, see `GF17Squared`).
+///
+/// We use `x^2 - z`, where `z` is the smallest quadratic non-residue: since `z`
+/// has no square root, `x^2 - z` has no root, and a polynomial of degree 2
+/// without roots is irreducible.
+pub fn irreducible_polynomial_deg2() -> Polynomial {
+    let z = (2..17u8)
+        .map(BaseField::from)
+        .find(|z| !z.is_quadratic_residue())
+        .expect("half of the non-zero elements are non-residues");
+
+    let poly = Polynomial::new(vec![-z, BaseField::zero(), BaseField::one()]);
+    debug_assert!(poly.roots().is_empty());
+
+    poly
+}
+
+/// An element of the extension field `GF(17^2)`, represented as a polynomial
+/// over `BaseField` of degree at most 1, modulo `irreducible_polynomial_deg2()`.
+///
+/// Since the irreducible polynomial has no roots, every non-zero element is
+/// invertible, which is what makes the quotient a field (with 289 elements)
+/// rather than just a ring.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GF17Squared {
+    // Always reduced modulo `irreducible_polynomial_deg2()`, and trimmed
+    poly: Polynomial,
+}
+
+impl GF17Squared {
+    /// Returns the element represented by `poly`, reduced modulo
+    /// `irreducible_polynomial_deg2()`
+    pub fn new(poly: Polynomial) -> Self {
+        let (_, remainder) = poly_div(&poly, &irreducible_polynomial_deg2())
+            .expect("the irreducible polynomial is not zero");
+
+        Self {
+            poly: remainder.trimmed(),
+        }
+    }
+
+    pub fn zero() -> Self {
+        Self::new(Polynomial::zero())
+    }
+
+    pub fn one() -> Self {
+        Self::new(Polynomial::one())
+    }
+
+    /// The coefficients `[a, b]` of the element `a + bt`, where `t` is a root
+    /// of `irreducible_polynomial_deg2()`. Trailing zeros are trimmed.
+    pub fn coefficients(&self) -> &[BaseField] {
+        self.poly.coefficients()
+    }
+}
+
+impl From<BaseField> for GF17Squared {
+    fn from(value: BaseField) -> Self {
+        Self::new(Polynomial::new(vec![value]))
+    }
+}
+
+impl Add for GF17Squared {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Self::new(self.poly + rhs.poly)
+    }
+}
+
+impl Sub for GF17Squared {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Self::new(self.poly - rhs.poly)
+    }
+}
+
+impl Neg for GF17Squared {
+    type Output = Self;
+
+    fn neg(self) -> Self::Output {
+        Self::zero() - self
+    }
+}
+
+impl Mul for GF17Squared {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        Self::new(self.poly * rhs.poly)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn irreducible_polynomial() {
+        let poly = irreducible_polynomial_deg2();
+
+        assert_eq!(poly.degree(), 2);
+        assert!(poly.roots().is_empty());
+    }
+
+    #[test]
+    pub fn root_of_irreducible_polynomial() {
+        // t^2 - z = 0, where t is the element represented by the polynomial x
+        let t = GF17Squared::new(Polynomial::new(vec![0.into(), 1.into()]));
+        let z = -irreducible_polynomial_deg2().coefficients()[0];
+
+        assert_eq!(t.clone() * t, GF17Squared::from(z));
+    }
+
+    #[test]
+    pub fn base_field_embedding() {
+        for a in 0..17u8 {
+            for b in 0..17u8 {
+                let (a, b) = (BaseField::from(a), BaseField::from(b));
+
+                assert_eq!(
+                    GF17Squared::from(a) * GF17Squared::from(b),
+                    GF17Squared::from(a * b)
+                );
+                assert_eq!(
+                    GF17Squared::from(a) + GF17Squared::from(b),
+                    GF17Squared::from(a + b)
+                );
+            }
+        }
+    }
+
+    #[test]
+    pub fn inverses_exist() {
+        let elements: Vec<GF17Squared> = (0..17u8)
+            .flat_map(|a| {
+                (0..17u8).map(move |b| GF17Squared::new(Polynomial::new(vec![a.into(), b.into()])))
+            })
+            .collect();
+        assert_eq!(elements.len(), 289);
+
+        // Elements outside of `BaseField` have an inverse (up to a factor in
+        // `BaseField`, it suffices to check the elements a + t)
+        for x in elements
+            .iter()
+            .filter(|x| x.coefficients().get(1) == Some(&BaseField::one()))
+        {
+            assert!(
+                elements
+                    .iter()
+                    .any(|y| x.clone() * y.clone() == GF17Squared::one()),
+                "{x:?} has no inverse"
+            );
+        }
+    }
+}
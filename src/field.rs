@@ -1,6 +1,6 @@
 use std::{
     fmt::Display,
-    ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Sub},
+    ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub},
 };
 
 const PRIME: u8 = 17;
@@ -8,7 +8,12 @@ const PRIME: u8 = 17;
 /// Represents an element of the prime field with prime 17.
 /// This group contains a multiplicative group of 16 elements,
 /// and cyclic subgroups of size 4 and 8.
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+///
+/// With the `serde` feature, an element is serialized as a single `u8` in the
+/// range [0, 16]. Deserialized values are reduced modulo 17.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(from = "u8", into = "u8"))]
 pub struct BaseField {
     element: u8,
 }
@@ -29,9 +34,8 @@ impl BaseField {
     }
 
     pub fn square(&self) -> Self {
-        Self {
-            element: (self.element * self.element) % PRIME,
-        }
+        // Note: we can't multiply the `u8`s directly, as e.g. 16 * 16 overflows
+        *self * *self
     }
 
     /// Returns the multiplicative inverse for elements in the subgroup
@@ -58,11 +62,21 @@ impl BaseField {
         BaseField::from(-1) * *self
     }
 
+    /// Computes `self^exponent` using square-and-multiply: we go through the
+    /// bits of `exponent` from the least significant, squaring the base at
+    /// every step, and multiplying it into the result when the bit is set.
     pub fn exp(self, exponent: u8) -> Self {
         let mut result = Self::one();
+        let mut base = self;
+        let mut exponent = exponent;
+
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                result *= base;
+            }
 
-        for _ in 0..exponent {
-            result *= self;
+            base = base.square();
+            exponent >>= 1;
         }
 
         result
@@ -96,6 +110,155 @@ impl BaseField {
     pub fn as_byte(&self) -> u8 {
         self.element
     }
+
+    /// Returns the representative of the element in the range [-8, 8]
+    pub fn as_i8_centered(&self) -> i8 {
+        if self.element > PRIME / 2 {
+            self.element as i8 - PRIME as i8
+        } else {
+            self.element as i8
+        }
+    }
+
+    /// Returns a wrapper that displays the element in its centered form (see
+    /// `as_i8_centered()`)
+    pub fn display_centered(&self) -> CenteredBaseField {
+        CenteredBaseField(*self)
+    }
+
+    /// Computes the Legendre symbol of the element: 0 if it is 0, 1 if it is a
+    /// (non-zero) square, and -1 otherwise. We find out whether the element is
+    /// a square by trying every element of the field.
+    pub fn legendre_symbol(&self) -> i8 {
+        if *self == Self::zero() {
+            return 0;
+        }
+
+        if (1..PRIME).any(|y| Self::from(y).square() == *self) {
+            1
+        } else {
+            -1
+        }
+    }
+
+    /// Same as `legendre_symbol()`, but uses Euler's criterion: `a^((p-1)/2)`
+    /// is 1 if `a` is a non-zero square, and -1 (i.e. `p-1`) if it isn't.
+    pub fn legendre_symbol_fast(&self) -> i8 {
+        let euler_criterion = self.exp((PRIME - 1) / 2);
+
+        if euler_criterion == Self::zero() {
+            0
+        } else if euler_criterion == Self::one() {
+            1
+        } else {
+            assert_eq!(euler_criterion, Self::from(-1));
+            -1
+        }
+    }
+
+    /// Returns `true` if the element is a non-zero square. By Euler's
+    /// criterion, this is the case if and only if `x^((p-1)/2) = 1`.
+    pub fn is_quadratic_residue(self) -> bool {
+        self.exp((PRIME - 1) / 2) == Self::one()
+    }
+
+    /// Returns a square root of the element if there is one, using the
+    /// Tonelli-Shanks algorithm. The other square root is its negation.
+    ///
+    /// We write `p - 1 = q * 2^s` with `q` odd (for us, `q = 1` and `s = 4`).
+    /// Starting from the candidate root `r = x^((q+1)/2)`, for which `r^2 = x *
+    /// x^q`, we repeatedly multiply `r` by powers of `c = z^q` (where `z` is a
+    /// non-residue) until `t = x^q` (the "error" factor) becomes 1.
+    pub fn sqrt(self) -> Option<Self> {
+        if self == Self::zero() {
+            return Some(self);
+        }
+        if !self.is_quadratic_residue() {
+            return None;
+        }
+
+        let s = (PRIME - 1).trailing_zeros();
+        let q = (PRIME - 1) >> s;
+
+        let z = (2..PRIME)
+            .map(Self::from)
+            .find(|z| !z.is_quadratic_residue())
+            .expect("half of the non-zero elements are non-residues");
+
+        let mut m = s;
+        let mut c = z.exp(q);
+        let mut t = self.exp(q);
+        let mut r = self.exp(q.div_ceil(2));
+
+        while t != Self::one() {
+            // Find the least `i` such that `t^(2^i) = 1`
+            let mut i = 0;
+            let mut t_2_i = t;
+            while t_2_i != Self::one() {
+                t_2_i = t_2_i.square();
+                i += 1;
+            }
+
+            // b = c^(2^(m-i-1))
+            let mut b = c;
+            for _ in 0..m - i - 1 {
+                b = b.square();
+            }
+
+            m = i;
+            c = b.square();
+            t *= c;
+            r *= b;
+        }
+
+        Some(r)
+    }
+
+    /// Returns 1 for `true`, and 0 for `false`
+    pub fn from_bool(b: bool) -> Self {
+        if b {
+            Self::one()
+        } else {
+            Self::zero()
+        }
+    }
+}
+
+/// Computes the multiplicative inverse of every element of `elements`, using a
+/// single call to `mult_inv()` (Montgomery's trick). We compute the prefix
+/// products `p_i = e_0 * ... * e_i`, and invert the last one. Then, going
+/// backwards, `e_i^-1 = p_{i-1} * p_i^-1`, and `p_{i-1}^-1 = e_i * p_i^-1`.
+///
+/// Panics if any element is 0.
+pub fn batch_mult_inv(elements: &[BaseField]) -> Vec<BaseField> {
+    let mut prefix_products = Vec::with_capacity(elements.len());
+    let mut product = BaseField::one();
+    for element in elements {
+        product *= *element;
+        prefix_products.push(product);
+    }
+
+    let mut inverses = vec![BaseField::zero(); elements.len()];
+    // Inverse of the prefix product up to the current element
+    let mut prefix_product_inv = product.mult_inv();
+    for i in (0..elements.len()).rev() {
+        let previous_prefix_product = if i == 0 {
+            BaseField::one()
+        } else {
+            prefix_products[i - 1]
+        };
+
+        inverses[i] = previous_prefix_product * prefix_product_inv;
+        prefix_product_inv *= elements[i];
+    }
+
+    inverses
+}
+
+impl From<bool> for BaseField {
+    fn from(b: bool) -> Self {
+        Self::from_bool(b)
+    }
 }
 
 impl From<u8> for BaseField {
@@ -153,6 +316,22 @@ impl Sub for BaseField {
     }
 }
 
+impl Neg for BaseField {
+    type Output = Self;
+
+    fn neg(self) -> Self::Output {
+        self.minus()
+    }
+}
+
+impl Neg for &BaseField {
+    type Output = BaseField;
+
+    fn neg(self) -> Self::Output {
+        self.minus()
+    }
+}
+
 impl Mul for BaseField {
     type Output = Self;
 
@@ -202,10 +381,25 @@ impl Display for BaseField {
     }
 }
 
+/// Displays a `BaseField` element in the range [-8, 8] rather than [0, 16];
+/// e.g. 16 is displayed as -1.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CenteredBaseField(pub BaseField);
+
+impl Display for CenteredBaseField {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0.as_i8_centered())
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use std::collections::HashSet;
+
     use super::*;
 
+    use crate::domain::{DOMAIN_LDE, DOMAIN_TRACE};
+
     #[test]
     fn test_from_i32() {
         let ele = BaseField::from(-1);
@@ -229,6 +423,15 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_square() {
+        for i in 0..PRIME {
+            let fel = BaseField::from(i);
+
+            assert_eq!(fel.square(), fel * fel);
+        }
+    }
+
     #[test]
     fn test_div() {
         for i in 1..PRIME {
@@ -265,6 +468,60 @@ mod tests {
         assert_eq!(field.exp(PRIME - 1), BaseField::one());
     }
 
+    #[test]
+    fn test_exp_matches_iterated_mult() {
+        for i in 1..PRIME {
+            let fel = BaseField::from(i);
+
+            let mut expected = BaseField::one();
+            for e in 0..=16u8 {
+                assert_eq!(fel.exp(e), expected, "{fel}^{e}");
+
+                expected *= fel;
+            }
+        }
+    }
+
+    #[test]
+    fn test_batch_mult_inv() {
+        let inverses = batch_mult_inv(&DOMAIN_TRACE);
+        assert_eq!(inverses.len(), DOMAIN_TRACE.len());
+
+        for (element, inverse) in DOMAIN_TRACE.iter().zip(inverses) {
+            assert_eq!(inverse, element.mult_inv());
+        }
+
+        let all_nonzero: Vec<BaseField> = (1..PRIME).map(BaseField::from).collect();
+        for (element, inverse) in all_nonzero.iter().zip(batch_mult_inv(&all_nonzero)) {
+            assert_eq!(inverse, element.mult_inv());
+        }
+
+        assert!(batch_mult_inv(&[]).is_empty());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_batch_mult_inv_zero() {
+        batch_mult_inv(&[BaseField::one(), BaseField::zero()]);
+    }
+
+    #[test]
+    fn test_sqrt() {
+        assert_eq!(BaseField::zero().sqrt(), Some(BaseField::zero()));
+
+        for i in 1..PRIME {
+            let fel = BaseField::from(i);
+
+            assert_eq!(fel.is_quadratic_residue(), fel.legendre_symbol() == 1);
+
+            if fel.is_quadratic_residue() {
+                assert_eq!(fel.sqrt().map(|r| r * r), Some(fel), "sqrt({fel})");
+            } else {
+                assert_eq!(fel.sqrt(), None, "sqrt({fel})");
+            }
+        }
+    }
+
     #[test]
     fn test_mult_inv() {
         for i in 1..PRIME {
@@ -282,4 +539,65 @@ mod tests {
             assert_eq!(BaseField::zero(), fel + fel.minus());
         }
     }
+
+    #[test]
+    fn test_hash_set() {
+        let trace_domain: HashSet<BaseField> = DOMAIN_TRACE.iter().copied().collect();
+        assert_eq!(trace_domain.len(), DOMAIN_TRACE.len());
+
+        // The domains are disjoint
+        for x in &DOMAIN_LDE {
+            assert!(!trace_domain.contains(x));
+        }
+    }
+
+    #[test]
+    fn test_neg() {
+        for i in 0..PRIME {
+            let fel = BaseField::from(i);
+
+            assert_eq!(-fel, fel.minus());
+            assert_eq!(-&fel, fel.minus());
+            assert_eq!(-(-fel), fel);
+        }
+    }
+
+    #[test]
+    fn test_from_bool() {
+        assert_eq!(BaseField::from_bool(true), BaseField::one());
+        assert_eq!(BaseField::from_bool(false), BaseField::zero());
+        assert_eq!(
+            BaseField::from(true) + BaseField::from(false),
+            BaseField::one()
+        );
+    }
+
+    #[test]
+    fn test_legendre_symbol() {
+        assert_eq!(BaseField::zero().legendre_symbol(), 0);
+        assert_eq!(BaseField::one().legendre_symbol(), 1);
+        // 3 is a generator of the multiplicative group, and hence not a square
+        assert_eq!(BaseField::from(3).legendre_symbol(), -1);
+
+        for i in 0..PRIME {
+            let fel = BaseField::from(i);
+
+            assert_eq!(fel.legendre_symbol_fast(), fel.legendre_symbol());
+        }
+    }
+
+    #[test]
+    pub fn display_centered() {
+        assert_eq!(CenteredBaseField(BaseField::from(16)).to_string(), "-1");
+        assert_eq!(BaseField::from(16).display_centered().to_string(), "-1");
+        assert_eq!(BaseField::from(8).display_centered().to_string(), "8");
+        assert_eq!(BaseField::from(9).display_centered().to_string(), "-8");
+        assert_eq!(BaseField::zero().display_centered().to_string(), "0");
+
+        for i in 0..PRIME {
+            let x = BaseField::from(i);
+            assert!((-8..=8).contains(&x.as_i8_centered()));
+            assert_eq!(BaseField::from(x.as_i8_centered() as i32), x);
+        }
+    }
 }
@@ -0,0 +1,936 @@
+use std::iter;
+
+use anyhow::{bail, Result};
+
+use crate::{
+    domain::{DynamicDomain, DOMAIN_LDE},
+    field::BaseField,
+    hash::HashAlgorithm,
+    merkle::{MerklePath, MerkleRoot, MerkleTree},
+    poly::Polynomial,
+    verifier::VerificationError,
+};
+
+/// A single FRI layer: a polynomial, its evaluations over the layer's domain,
+/// and the Merkle tree of these evaluations (which is what gets committed).
+#[derive(Clone, Debug)]
+pub struct FriLayer {
+    pub domain: DynamicDomain,
+    pub polynomial: Polynomial,
+    pub evaluations: Vec<BaseField>,
+    pub merkle_tree: MerkleTree,
+}
+
+impl FriLayer {
+    /// Evaluates `polynomial` over `domain`, and builds the Merkle tree of the
+    /// evaluations with `hash_algorithm`
+    pub fn new(
+        domain: DynamicDomain,
+        polynomial: Polynomial,
+        hash_algorithm: HashAlgorithm,
+    ) -> Self {
+        let evaluations = polynomial.eval_domain(&domain);
+        let merkle_tree = MerkleTree::new_with_hash(&evaluations, hash_algorithm);
+
+        Self {
+            domain,
+            polynomial,
+            evaluations,
+            merkle_tree,
+        }
+    }
+}
+
+/// Returns the next FRI layer, whose Merkle tree uses the same hash algorithm
+/// as `layer`'s
+pub fn fri_step(layer: &FriLayer, beta: BaseField) -> FriLayer {
+    // The domain of the next FRI layer is (the first or second) half of the
+    // current domain, where every element is squared. Both the first or second
+    // half squared result in the same domain. For example, given a domain with generator g,
+    //
+    // dom = {g^0, g^1, g^2, g^3}
+    // first_half = {g^0, g^1}
+    // first_half_squared = {g^0, g^2}
+    //
+    // second_half = {g^2, g^3}
+    // second_half_squared = {g^4, g^6} = {g^0, g^2}
+    // ^ The second equality is true because g^4 = 1 (by definition of g being the generator)
+    //
+    // Refer to Stark 101 part 3 for more information.
+    FriLayer::new(
+        layer.domain.halve(),
+        layer.polynomial.clone().fri_step(beta),
+        layer.merkle_tree.hash_algorithm(),
+    )
+}
+
+/// Configuration of the FRI folding.
+///
+/// Note: the STARK itself (see `generate_proof()` and `verify()`) always folds
+/// by a factor of 2, since its FRI layers are committed over domains that are
+/// halved at each step. This configures the folding of `FriConfig::fold()`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FriConfig {
+    /// The number of sub-polynomials combined at each FRI step (see
+    /// `fri_step_kx()`). The domain, as well as the degree bound, is divided
+    /// by this factor at each step, so a folding factor of 4 needs half as
+    /// many layers as a folding factor of 2.
+    pub folding_factor: usize,
+}
+
+impl Default for FriConfig {
+    fn default() -> Self {
+        Self { folding_factor: 2 }
+    }
+}
+
+impl FriConfig {
+    /// Folds `poly` by `folding_factor`, given `folding_factor - 1` betas (see
+    /// `fri_step_kx()`)
+    pub fn fold(&self, poly: Polynomial, betas: &[BaseField]) -> Polynomial {
+        fri_step_kx(poly, self.folding_factor, betas)
+    }
+
+    /// The number of FRI steps that fold a polynomial of degree `degree` down
+    /// to a constant. Every step divides the number of coefficients by
+    /// `folding_factor` (rounding up).
+    ///
+    /// Panics if `folding_factor < 2`.
+    pub fn num_steps(&self, degree: usize) -> usize {
+        assert!(
+            self.folding_factor >= 2,
+            "FRI folding factor must be at least 2, got {}",
+            self.folding_factor
+        );
+
+        let mut num_coefficients = degree + 1;
+        let mut num_steps = 0;
+        while num_coefficients > 1 {
+            num_coefficients = num_coefficients.div_ceil(self.folding_factor);
+            num_steps += 1;
+        }
+
+        num_steps
+    }
+}
+
+/// Returns the number of queries needed for `target_bits` bits of security,
+/// under the conjecture that each query contributes `log2(lde_blowup)` bits:
+///
+///   ceil(target_bits / log2(lde_blowup))
+///
+/// The conjectured bound only depends on the rate `1 / lde_blowup`, and not on
+/// `_trace_degree` itself.
+///
+/// Note that this only accounts for the query phase. In this crate, the field
+/// has 17 elements, so a cheating prover can guess any random challenge (e.g.
+/// a FRI beta) with probability 1/17, which caps the security at about 4 bits
+/// no matter how many queries there are. Also, the LDE domain has 8 elements,
+/// so beyond a handful of queries, the query indices repeat and don't add any
+/// security. 80 bits of security require a field (or extension field) of
+/// size well above 2^80.
+///
+/// Panics if `lde_blowup < 2`.
+pub fn fri_optimal_queries(_trace_degree: usize, lde_blowup: usize, target_bits: u32) -> usize {
+    assert!(
+        lde_blowup >= 2,
+        "blowup factor must be at least 2, got {lde_blowup}"
+    );
+
+    let bits_per_query = (lde_blowup as f64).log2();
+
+    (target_bits as f64 / bits_per_query).ceil() as usize
+}
+
+/// A target number of bits of security (see `fri_optimal_queries()`)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SecurityLevel {
+    Bits64,
+    Bits80,
+    Bits100,
+    Bits128,
+}
+
+impl SecurityLevel {
+    pub fn bits(self) -> u32 {
+        match self {
+            SecurityLevel::Bits64 => 64,
+            SecurityLevel::Bits80 => 80,
+            SecurityLevel::Bits100 => 100,
+            SecurityLevel::Bits128 => 128,
+        }
+    }
+
+    /// Returns the number of queries needed to reach this level for a
+    /// polynomial of degree `degree`, evaluated over an LDE domain `blowup`
+    /// times the size of its trace domain (see `fri_optimal_queries()`). With
+    /// the domains of this crate, `blowup` is `DOMAIN_LDE.len() /
+    /// DOMAIN_TRACE.len()`, i.e. 2.
+    ///
+    /// The LDE domain is a multiplicative subgroup (or a coset of one) whose
+    /// size is a power of 2, so it can hold at most the largest power of 2
+    /// dividing `field_size - 1` elements. For example, GF(17) allows domains
+    /// of up to 16 elements, so a blowup factor of at most 4 for a degree 3
+    /// polynomial.
+    ///
+    /// Panics if `field_size < 2`, if `blowup < 2`, or if the field can't hold
+    /// an LDE domain of that size.
+    pub fn queries_needed(self, field_size: u64, degree: usize, blowup: usize) -> usize {
+        assert!(
+            field_size >= 2,
+            "a field has at least 2 elements, got {field_size}"
+        );
+        let max_domain_size = 1u64 << (field_size - 1).trailing_zeros();
+        let trace_domain_size = (degree + 1).next_power_of_two() as u64;
+        assert!(
+            trace_domain_size.saturating_mul(blowup as u64) <= max_domain_size,
+            "a field of {field_size} elements can't hold an LDE domain of {trace_domain_size} * {blowup} elements"
+        );
+
+        fri_optimal_queries(degree, blowup, self.bits())
+    }
+
+    /// Returns whether a field of `field_size` elements can provide this level
+    /// of security. Every random challenge is drawn from the field, and a
+    /// cheating prover guesses a challenge with probability `1 / field_size`.
+    /// Hence, the field needs at least `2^bits` elements, no matter how many
+    /// queries there are.
+    ///
+    /// Note that no field of at most `u64::MAX` elements reaches any of the
+    /// levels; this is why 64-bit fields (e.g. Goldilocks) draw their
+    /// challenges from an extension field.
+    pub fn is_achievable_in_field(self, field_size: u64) -> bool {
+        field_size
+            .checked_ilog2()
+            .is_some_and(|bits| bits >= self.bits())
+    }
+}
+
+/// Folds `poly` by a factor of `k`: `poly` is split into `k` parts (see
+/// `Polynomial::split_into_k_parts()`), which are combined as
+///
+///   poly_0 + betas[0] * poly_1 + ... + betas[k - 2] * poly_{k-1}
+///
+/// For `k = 2`, this is `Polynomial::fri_step()`. Passing the powers `beta,
+/// beta^2, ..., beta^(k-1)` of a single random element gives `sum_j beta^j *
+/// poly_j`; passing `b_1, b_2, b_1 * b_2` for `k = 4` gives the same result as
+/// 2 consecutive FRI steps with `b_1` and `b_2`.
+///
+/// Panics if `k < 2`, or if there aren't exactly `k - 1` betas.
+pub fn fri_step_kx(poly: Polynomial, k: usize, betas: &[BaseField]) -> Polynomial {
+    assert!(k >= 2, "FRI folding factor must be at least 2, got {k}");
+    assert_eq!(
+        betas.len(),
+        k - 1,
+        "expected {} betas, got {}",
+        k - 1,
+        betas.len()
+    );
+
+    let mut parts = poly.split_into_k_parts(k).into_iter();
+    let first_part = parts.next().unwrap();
+
+    parts
+        .zip(betas)
+        .fold(first_part, |acc, (part, beta)| acc + part * *beta)
+}
+
+/// Builds every FRI layer, starting from `poly` evaluated over
+/// `initial_domain`, and folding once for each element of `betas`. The first
+/// layer is `poly` itself. The Merkle trees use the default hash algorithm.
+///
+/// When given as many betas as it takes to fold `poly` down to a constant, the
+/// last layer has 2 equal evaluations.
+pub fn build_all_layers(
+    poly: Polynomial,
+    initial_domain: &DynamicDomain,
+    betas: &[BaseField],
+) -> Vec<FriLayer> {
+    let mut layers = vec![FriLayer::new(
+        initial_domain.clone(),
+        poly,
+        HashAlgorithm::default(),
+    )];
+
+    for beta in betas {
+        let next_layer = fri_step(layers.last().unwrap(), *beta);
+        layers.push(next_layer);
+    }
+
+    layers
+}
+
+/// Performs a DEEP-FRI step on `poly`: divides out the out-of-domain
+/// evaluation at `z`, and folds the quotient with `beta` (see
+/// `Polynomial::fri_step()`). That is, the next layer is the FRI step of
+///
+///   q(x) = (poly(x) - y_z) / (x - z)
+///
+/// which is a polynomial only if `y_z = poly(z)`; otherwise, the remainder of
+/// the division is dropped. Hence, this also returns `poly(z)`, for the
+/// verifier to check against the claimed `y_z`.
+///
+/// `z` should be outside of the domain over which `poly` is committed, such
+/// that the verifier can compute `q(x)` from every opened `poly(x)` (see
+/// `deep_quotient_at()`).
+pub fn deep_fri_step(
+    poly: &Polynomial,
+    z: BaseField,
+    y_z: BaseField,
+    beta: BaseField,
+) -> (Polynomial, BaseField) {
+    let (quotient, remainder) = (poly.clone() - Polynomial::new(vec![y_z])).div_by_linear(z);
+
+    (quotient.fri_step(beta), remainder + y_z)
+}
+
+/// Returns the value of the DEEP quotient `q(x) = (poly(x) - y_z) / (x - z)`
+/// (see `deep_fri_step()`) at `x`, given the value `poly_x` of the committed
+/// polynomial at `x`. This is how the verifier checks a DEEP-FRI layer using
+/// the opened (Merkle verified) values of `poly`.
+///
+/// Precondition: `x != z`
+pub fn deep_quotient_at(
+    poly_x: BaseField,
+    x: BaseField,
+    z: BaseField,
+    y_z: BaseField,
+) -> BaseField {
+    (poly_x - y_z) / (x - z)
+}
+
+/// The commitments of a FRI proof: the Merkle roots of every committed layer
+/// after the first one (whose root is committed separately, see
+/// `verify_fri()`), and the value of the last layer. The last layer is a
+/// constant, so it is sent in the clear instead of being committed.
+///
+/// With the `serde` feature, the fields are serialized in declaration order.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FriCommitment {
+    #[cfg_attr(feature = "serde", serde(with = "crate::merkle::serde_hashes"))]
+    pub layer_roots: Vec<MerkleRoot>,
+    pub final_value: BaseField,
+}
+
+/// A FRI proof, generated by `generate_fri_proof()` and checked by
+/// `verify_fri()`.
+///
+/// `query_paths` has one entry per query. For a query at `x` in the first
+/// layer's domain, the entry contains the opened values, along with their
+/// Merkle path:
+/// 1. The first layer at `x`
+/// 2. The first layer at `-x`
+/// 3. For each layer in `commitment.layer_roots`, the layer at `-x^(2^i)`,
+///    where `i` starts at 1
+///
+/// With the `serde` feature, the fields are serialized in declaration order.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FriProof {
+    pub commitment: FriCommitment,
+    pub query_paths: Vec<Vec<(BaseField, MerklePath)>>,
+}
+
+/// Generates the FRI proof for `layers` (as built by `fri_step()`), opened at
+/// every index of `query_indices` (in the domain of the first layer). The
+/// last layer must be constant.
+///
+/// Fails if there are fewer than 2 layers, or if a query index is out of
+/// bounds.
+pub fn generate_fri_proof(layers: &[FriLayer], query_indices: &[usize]) -> Result<FriProof> {
+    let [first_layer, committed_layers @ .., last_layer] = layers else {
+        bail!("expected at least 2 FRI layers, got {}", layers.len());
+    };
+
+    let domain_len = first_layer.domain.len();
+    if let Some(query_idx) = query_indices.iter().find(|idx| **idx >= domain_len) {
+        bail!("query index {query_idx} out of bounds ({domain_len} elements)");
+    }
+
+    let query_paths = query_indices
+        .iter()
+        .map(|&query_idx| {
+            let mut openings = vec![(
+                first_layer.evaluations[query_idx],
+                MerklePath::new(&first_layer.merkle_tree, query_idx)?,
+            )];
+
+            // Index of x^(2^i) in the domain of layer i
+            let mut x_idx = query_idx;
+            for layer in iter::once(first_layer).chain(committed_layers) {
+                let x = layer.domain[x_idx];
+                let minus_x_idx = layer
+                    .domain
+                    .index_of(-x)
+                    .expect("FRI domains are closed under negation");
+
+                openings.push((
+                    layer.evaluations[minus_x_idx],
+                    MerklePath::new(&layer.merkle_tree, minus_x_idx)?,
+                ));
+
+                // x^2 = (-x)^2 is in the first half of the next domain (see `fri_step()`)
+                x_idx %= layer.domain.len() / 2;
+            }
+
+            Ok(openings)
+        })
+        .collect::<Result<_>>()?;
+
+    Ok(FriProof {
+        commitment: FriCommitment {
+            layer_roots: committed_layers
+                .iter()
+                .map(|layer| layer.merkle_tree.root)
+                .collect(),
+            final_value: last_layer.evaluations[0],
+        },
+        query_paths,
+    })
+}
+
+/// Verifies `proof`, where the first layer is a polynomial evaluated over
+/// `DOMAIN_LDE` and committed to in `cp_commitment` (in the STARK, the
+/// composition polynomial). `betas` are the random elements used to fold each
+/// layer but the last (i.e. one more than the number of layer roots).
+/// `query_indices` are the indices in `DOMAIN_LDE` that the verifier drew
+/// from the channel: the i-th query of `proof` must open the first layer at
+/// `query_indices[i]`, so that the prover can't pick the points it's checked
+/// at.
+///
+/// Merkle trees are expected to use the default hash algorithm (see
+/// `verify_fri_with_hash()`).
+pub fn verify_fri(
+    proof: &FriProof,
+    cp_commitment: MerkleRoot,
+    betas: &[BaseField],
+    query_indices: &[usize],
+) -> Result<(), VerificationError> {
+    verify_fri_with_hash(
+        proof,
+        cp_commitment,
+        betas,
+        query_indices,
+        HashAlgorithm::default(),
+    )
+}
+
+/// Same as `verify_fri()`, for Merkle trees built with `hash_algorithm`
+pub fn verify_fri_with_hash(
+    proof: &FriProof,
+    cp_commitment: MerkleRoot,
+    betas: &[BaseField],
+    query_indices: &[usize],
+    hash_algorithm: HashAlgorithm,
+) -> Result<(), VerificationError> {
+    let layer_roots = &proof.commitment.layer_roots;
+
+    // Every fold halves the domain, which must keep at least 1 element
+    if betas.len() != layer_roots.len() + 1 || betas.len() > DOMAIN_LDE.len().ilog2() as usize {
+        return Err(VerificationError::FriCheckFailed);
+    }
+
+    if proof.query_paths.is_empty() {
+        return Err(VerificationError::NoQueries);
+    }
+    if proof.query_paths.len() != query_indices.len() {
+        return Err(VerificationError::QueryIndexMismatch);
+    }
+
+    for (openings, query_idx) in proof.query_paths.iter().zip(query_indices) {
+        let [(cp_x, cp_x_path), (cp_minus_x, cp_minus_x_path), layer_openings @ ..] = &openings[..]
+        else {
+            return Err(VerificationError::FriCheckFailed);
+        };
+        if layer_openings.len() != layer_roots.len() {
+            return Err(VerificationError::FriCheckFailed);
+        }
+
+        let mut domain = DynamicDomain::from(&DOMAIN_LDE);
+        let mut x_idx = opened_index(cp_x_path, &domain)?;
+        if x_idx != *query_idx {
+            return Err(VerificationError::QueryIndexMismatch);
+        }
+        if !cp_x_path.verify_inclusion_with_hash(*cp_x, cp_commitment, hash_algorithm) {
+            return Err(VerificationError::MerkleProofFailed { which: "cp_x" });
+        }
+
+        let minus_x_openings =
+            iter::once((cp_minus_x, cp_minus_x_path, cp_commitment, "cp_minus_x")).chain(
+                layer_openings
+                    .iter()
+                    .zip(layer_roots)
+                    .map(|((value, path), root)| (value, path, *root, "fri_layer_minus_x")),
+            );
+
+        // Value of layer i at x^(2^i)
+        let mut layer_x = *cp_x;
+        for ((layer_minus_x, minus_x_path, root, which), beta) in minus_x_openings.zip(betas) {
+            let x = domain[x_idx];
+            if domain.index_of(-x) != Some(opened_index(minus_x_path, &domain)?) {
+                return Err(VerificationError::QueryIndexMismatch);
+            }
+            if !minus_x_path.verify_inclusion_with_hash(*layer_minus_x, root, hash_algorithm) {
+                return Err(VerificationError::MerkleProofFailed { which });
+            }
+
+            layer_x = fold(layer_x, *layer_minus_x, x, *beta);
+
+            domain = domain.halve();
+            x_idx %= domain.len();
+        }
+
+        if layer_x != proof.commitment.final_value {
+            return Err(VerificationError::FriCheckFailed);
+        }
+    }
+
+    Ok(())
+}
+
+/// Verifies a single query of a DEEP-FRI step (see `deep_fri_step()`) on a
+/// polynomial committed over `DOMAIN_LDE` in `commitment`. `openings` are the
+/// values of the polynomial at `x = DOMAIN_LDE[query_idx]` and at `-x`, along
+/// with their Merkle paths (as in `FriProof::query_paths`). `ood_evaluation`
+/// is the claimed `(z, y_z)`, and `next_value` is the value of the next layer
+/// at `x^2` (or the value of the last layer).
+///
+/// The verifier recomputes the DEEP quotient at `x` and `-x` from the opened
+/// values (see `deep_quotient_at()`), folds them with `beta`, and checks the
+/// result against `next_value`. If `y_z` isn't the evaluation at `z`, the
+/// quotient isn't a polynomial, and the folded value doesn't match.
+pub fn verify_deep_fri_query(
+    openings: &[(BaseField, MerklePath)],
+    commitment: MerkleRoot,
+    query_idx: usize,
+    ood_evaluation: (BaseField, BaseField),
+    beta: BaseField,
+    next_value: BaseField,
+    hash_algorithm: HashAlgorithm,
+) -> Result<(), VerificationError> {
+    let [(poly_x, poly_x_path), (poly_minus_x, poly_minus_x_path)] = openings else {
+        return Err(VerificationError::FriCheckFailed);
+    };
+    let (z, y_z) = ood_evaluation;
+
+    let domain = DynamicDomain::from(&DOMAIN_LDE);
+    // The quotient is only defined outside of the domain
+    if domain.contains(z) {
+        return Err(VerificationError::FriCheckFailed);
+    }
+
+    if opened_index(poly_x_path, &domain)? != query_idx {
+        return Err(VerificationError::QueryIndexMismatch);
+    }
+    let x = domain[query_idx];
+    if domain.index_of(-x) != Some(opened_index(poly_minus_x_path, &domain)?) {
+        return Err(VerificationError::QueryIndexMismatch);
+    }
+
+    if !poly_x_path.verify_inclusion_with_hash(*poly_x, commitment, hash_algorithm) {
+        return Err(VerificationError::MerkleProofFailed { which: "poly_x" });
+    }
+    if !poly_minus_x_path.verify_inclusion_with_hash(*poly_minus_x, commitment, hash_algorithm) {
+        return Err(VerificationError::MerkleProofFailed {
+            which: "poly_minus_x",
+        });
+    }
+
+    let q_x = deep_quotient_at(*poly_x, x, z, y_z);
+    let q_minus_x = deep_quotient_at(*poly_minus_x, -x, z, y_z);
+
+    if fold(q_x, q_minus_x, x, beta) != next_value {
+        return Err(VerificationError::FriCheckFailed);
+    }
+
+    Ok(())
+}
+
+/// Returns the index in `domain` of the leaf that `path` opens. Fails if the
+/// path doesn't have the length of a path in a tree of `domain.len()` leaves.
+fn opened_index(path: &MerklePath, domain: &DynamicDomain) -> Result<usize, VerificationError> {
+    if path.len() != domain.len().ilog2() as usize {
+        return Err(VerificationError::QueryIndexMismatch);
+    }
+
+    Ok(path.leaf_index())
+}
+
+/// Computes the evaluations of the next FRI layer over the next domain (i.e.
+/// the first half of `domain`, squared) directly from the `evaluations` of the
+/// current layer over `domain`, without going through coefficient form:
+///
+///   f_next(x^2) = (f(x) + f(-x)) / 2 + beta * (f(x) - f(-x)) / 2x
+///
+/// Precondition: `domain[i + n/2] = -domain[i]`, where `n` is the size of
+/// `domain`; this holds for our domains (see `fri_step()`).
+pub fn fold_evaluations(
+    domain: &[BaseField],
+    evaluations: &[BaseField],
+    beta: BaseField,
+) -> Vec<BaseField> {
+    assert_eq!(domain.len(), evaluations.len());
+    let half_len = domain.len() / 2;
+
+    (0..half_len)
+        .map(|idx| {
+            fold(
+                evaluations[idx],
+                evaluations[idx + half_len],
+                domain[idx],
+                beta,
+            )
+        })
+        .collect()
+}
+
+/// Returns the value of the next FRI layer at `x^2`, given the values `f_x`
+/// and `f_minus_x` of the current layer at `x` and `-x` (see
+/// `fold_evaluations()`).
+fn fold(f_x: BaseField, f_minus_x: BaseField, x: BaseField, beta: BaseField) -> BaseField {
+    let g_x_squared = (f_x + f_minus_x) / BaseField::from(2);
+    let h_x_squared = (f_x - f_minus_x) / (BaseField::from(2) * x);
+
+    g_x_squared + beta * h_x_squared
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        constraints::composition_polynomial, domain::DOMAIN_LDE, trace::TRACE_FIRST_ELEMENT,
+    };
+
+    #[test]
+    pub fn all_layers_consistent() {
+        let cp = composition_polynomial(TRACE_FIRST_ELEMENT, 5.into(), 11.into());
+        let betas: [BaseField; 2] = [3.into(), 7.into()];
+
+        let layers = build_all_layers(cp, &(&DOMAIN_LDE).into(), &betas);
+        assert_eq!(layers.len(), betas.len() + 1);
+
+        for (i, beta) in betas.iter().enumerate() {
+            let (domain, evals) = (&layers[i].domain, &layers[i].evaluations);
+            let (next_domain, next_evals) = (&layers[i + 1].domain, &layers[i + 1].evaluations);
+            assert_eq!(next_domain.len(), domain.len() / 2);
+
+            // next_layer(x^2) = (layer(x) + layer(-x)) / 2 + beta * (layer(x) - layer(-x)) / 2x
+            for idx in 0..next_domain.len() {
+                let x = domain[idx];
+                let layer_x = evals[idx];
+                let layer_minus_x = evals[idx + domain.len() / 2];
+
+                let g_x_squared = (layer_x + layer_minus_x) / BaseField::from(2);
+                let h_x_squared = (layer_x - layer_minus_x) / (BaseField::from(2) * x);
+
+                assert_eq!(next_domain[idx], x.exp(2));
+                assert_eq!(next_evals[idx], g_x_squared + *beta * h_x_squared);
+            }
+        }
+
+        let last_layer = layers.last().unwrap();
+        assert_eq!(last_layer.domain.len(), 2);
+        assert_eq!(last_layer.evaluations[0], last_layer.evaluations[1]);
+    }
+
+    #[test]
+    pub fn fri_step_commits_to_evaluations() {
+        let cp = composition_polynomial(TRACE_FIRST_ELEMENT, 5.into(), 11.into());
+        let layer = FriLayer::new((&DOMAIN_LDE).into(), cp, HashAlgorithm::Sha256);
+
+        let next_layer = fri_step(&layer, 3.into());
+
+        assert_eq!(next_layer.domain, layer.domain.halve());
+        assert_eq!(
+            next_layer.evaluations,
+            next_layer.polynomial.eval_domain(&next_layer.domain)
+        );
+        assert_eq!(
+            next_layer.merkle_tree.root,
+            MerkleTree::new_with_hash(&next_layer.evaluations, HashAlgorithm::Sha256).root
+        );
+    }
+
+    #[test]
+    pub fn fold_evaluations_matches_fri_step() {
+        let poly = composition_polynomial(TRACE_FIRST_ELEMENT, 5.into(), 11.into());
+        let domain = DynamicDomain::from(&DOMAIN_LDE);
+        let evaluations = poly.eval_domain(&domain);
+        let next_domain = domain.halve();
+
+        for beta in 0..17 {
+            let beta = BaseField::from(beta);
+            assert_eq!(
+                fold_evaluations(&domain, &evaluations, beta),
+                poly.clone().fri_step(beta).eval_domain(&next_domain)
+            );
+        }
+    }
+
+    #[test]
+    pub fn verify_fri_standalone() {
+        let cp = composition_polynomial(TRACE_FIRST_ELEMENT, 5.into(), 11.into());
+        let betas: [BaseField; 2] = [3.into(), 7.into()];
+        let layers = build_all_layers(cp, &(&DOMAIN_LDE).into(), &betas);
+        let cp_commitment = layers[0].merkle_tree.root;
+
+        let query_indices: Vec<usize> = (0..DOMAIN_LDE.len()).collect();
+        let proof = generate_fri_proof(&layers, &query_indices).unwrap();
+        assert_eq!(
+            proof.commitment.layer_roots,
+            vec![layers[1].merkle_tree.root]
+        );
+        assert_eq!(proof.query_paths.len(), DOMAIN_LDE.len());
+        assert_eq!(
+            verify_fri(&proof, cp_commitment, &betas, &query_indices),
+            Ok(())
+        );
+
+        // Wrong betas
+        assert_eq!(
+            verify_fri(&proof, cp_commitment, &[3.into(), 8.into()], &query_indices),
+            Err(VerificationError::FriCheckFailed)
+        );
+        assert_eq!(
+            verify_fri(&proof, cp_commitment, &betas[..1], &query_indices),
+            Err(VerificationError::FriCheckFailed)
+        );
+
+        // Wrong final value
+        let mut tampered_proof = proof.clone();
+        tampered_proof.commitment.final_value += BaseField::one();
+        assert_eq!(
+            verify_fri(&tampered_proof, cp_commitment, &betas, &query_indices),
+            Err(VerificationError::FriCheckFailed)
+        );
+
+        // Opening -x at the wrong index
+        let mut tampered_proof = proof.clone();
+        tampered_proof.query_paths[0][1] = tampered_proof.query_paths[0][0].clone();
+        assert_eq!(
+            verify_fri(&tampered_proof, cp_commitment, &betas, &query_indices),
+            Err(VerificationError::QueryIndexMismatch)
+        );
+
+        let mut tampered_proof = proof.clone();
+        tampered_proof.query_paths.clear();
+        assert_eq!(
+            verify_fri(&tampered_proof, cp_commitment, &betas, &[]),
+            Err(VerificationError::NoQueries)
+        );
+
+        // Valid openings, but not at the indices drawn by the verifier
+        let mut shifted_indices = query_indices.clone();
+        shifted_indices.rotate_left(1);
+        assert_eq!(
+            verify_fri(&proof, cp_commitment, &betas, &shifted_indices),
+            Err(VerificationError::QueryIndexMismatch)
+        );
+        assert_eq!(
+            verify_fri(&proof, cp_commitment, &betas, &query_indices[1..]),
+            Err(VerificationError::QueryIndexMismatch)
+        );
+
+        assert!(generate_fri_proof(&layers, &[DOMAIN_LDE.len()]).is_err());
+        assert!(generate_fri_proof(&layers[..1], &[0]).is_err());
+    }
+
+    #[test]
+    pub fn verify_fri_rejects_high_degree() {
+        // Changing a single evaluation of the composition polynomial yields a
+        // polynomial of degree 7
+        let cp = composition_polynomial(TRACE_FIRST_ELEMENT, 5.into(), 11.into());
+        let mut cp_lde = cp.eval_domain(&DOMAIN_LDE);
+        cp_lde[0] += BaseField::one();
+        let tampered_poly = Polynomial::lagrange_interp(&DOMAIN_LDE, &cp_lde).unwrap();
+
+        let betas: [BaseField; 2] = [3.into(), 7.into()];
+        let layers = build_all_layers(tampered_poly, &(&DOMAIN_LDE).into(), &betas);
+        let cp_commitment = layers[0].merkle_tree.root;
+
+        let query_indices: Vec<usize> = (0..DOMAIN_LDE.len()).collect();
+        let proof = generate_fri_proof(&layers, &query_indices).unwrap();
+        assert_eq!(
+            verify_fri(&proof, cp_commitment, &betas, &query_indices),
+            Err(VerificationError::FriCheckFailed)
+        );
+    }
+
+    #[test]
+    pub fn deep_fri_step_out_of_domain() {
+        let cp = composition_polynomial(TRACE_FIRST_ELEMENT, 5.into(), 11.into());
+        let domain = DynamicDomain::from(&DOMAIN_LDE);
+        let beta = BaseField::from(3);
+
+        // 2 is in neither the trace nor the LDE domain
+        let z = BaseField::from(2);
+        assert!(!domain.contains(z));
+        let y_z = cp.eval(z);
+
+        let (next_poly, poly_z) = deep_fri_step(&cp, z, y_z, beta);
+        assert_eq!(poly_z, y_z);
+
+        // The DEEP quotient has one degree less, so the folded polynomial has
+        // degree 1
+        assert_eq!(next_poly.clone().trimmed().degree(), 1);
+
+        // The verifier recomputes the quotient from the opened (and Merkle
+        // verified) values, and folds it as in regular FRI
+        let layer = FriLayer::new(domain.clone(), cp, HashAlgorithm::default());
+        let openings = |idx: usize| {
+            let minus_x_idx = (idx + domain.len() / 2) % domain.len();
+            [idx, minus_x_idx].map(|idx| {
+                (
+                    layer.evaluations[idx],
+                    MerklePath::new(&layer.merkle_tree, idx).unwrap(),
+                )
+            })
+        };
+        let verify_query = |idx: usize, y_z: BaseField| {
+            let next_value = next_poly.eval(domain[idx].exp(2));
+
+            verify_deep_fri_query(
+                &openings(idx),
+                layer.merkle_tree.root,
+                idx,
+                (z, y_z),
+                beta,
+                next_value,
+                HashAlgorithm::default(),
+            )
+        };
+
+        for idx in 0..domain.len() {
+            assert_eq!(verify_query(idx, y_z), Ok(()), "query {idx}");
+
+            // A wrong claimed evaluation at z is caught
+            assert_eq!(
+                verify_query(idx, y_z + BaseField::one()),
+                Err(VerificationError::FriCheckFailed),
+                "query {idx}"
+            );
+        }
+
+        // The openings must be at the query index
+        assert_eq!(
+            verify_deep_fri_query(
+                &openings(1),
+                layer.merkle_tree.root,
+                0,
+                (z, y_z),
+                beta,
+                next_poly.eval(domain[0].exp(2)),
+                HashAlgorithm::default(),
+            ),
+            Err(VerificationError::QueryIndexMismatch)
+        );
+
+        // A tampered opening is caught
+        let mut tampered_openings = openings(0);
+        tampered_openings[1].0 += BaseField::one();
+        assert_eq!(
+            verify_deep_fri_query(
+                &tampered_openings,
+                layer.merkle_tree.root,
+                0,
+                (z, y_z),
+                beta,
+                next_poly.eval(domain[0].exp(2)),
+                HashAlgorithm::default(),
+            ),
+            Err(VerificationError::MerkleProofFailed {
+                which: "poly_minus_x"
+            })
+        );
+    }
+
+    #[test]
+    pub fn fri_step_4x_matches_two_fri_steps() {
+        let cp = composition_polynomial(TRACE_FIRST_ELEMENT, 5.into(), 11.into());
+        let (b_1, b_2) = (BaseField::from(3), BaseField::from(7));
+
+        // A degree 3 polynomial folds down to a constant in a single step
+        let folded = fri_step_kx(cp.clone(), 4, &[b_1, b_2, b_1 * b_2]);
+        assert_eq!(folded.clone().trimmed().degree(), 0);
+        assert_eq!(folded, cp.clone().fri_step(b_1).fri_step(b_2));
+
+        // sum_j beta^j * poly_j, checked over the domain folded by 4
+        let beta = BaseField::from(5);
+        let folded = fri_step_kx(cp.clone(), 4, &[beta, beta.exp(2), beta.exp(3)]);
+        let parts = cp.split_into_k_parts(4);
+        for x in &DOMAIN_LDE {
+            let x_4 = x.exp(4);
+            let expected = (0..4)
+                .map(|j| beta.exp(j as u8) * parts[j].eval(x_4))
+                .fold(BaseField::zero(), |acc, term| acc + term);
+
+            assert_eq!(folded.eval(x_4), expected);
+        }
+
+        assert_eq!(fri_step_kx(cp.clone(), 2, &[b_1]), cp.clone().fri_step(b_1));
+        // A folding factor of 4 needs half as many steps
+        let config = FriConfig::default();
+        assert_eq!(config.folding_factor, 2);
+        assert_eq!(config.num_steps(cp.degree()), 2);
+        assert_eq!(config.num_steps(0), 0);
+
+        let config_4x = FriConfig { folding_factor: 4 };
+        assert_eq!(config_4x.num_steps(cp.degree()), 1);
+        assert_eq!(config_4x.num_steps(15), 2);
+        assert_eq!(
+            config_4x.fold(cp.clone(), &[b_1, b_2, b_1 * b_2]),
+            cp.clone().fri_step(b_1).fri_step(b_2)
+        );
+        assert_eq!(config.fold(cp.clone(), &[b_1]), cp.fri_step(b_1));
+    }
+
+    #[test]
+    pub fn fri_optimal_queries_for_target_security() {
+        // log2(17) ~ 4 bits per challenge, so these are only meaningful for the
+        // query phase (see `fri_optimal_queries()`)
+        assert_eq!(fri_optimal_queries(3, 2, 80), 80);
+        assert_eq!(fri_optimal_queries(3, 4, 80), 40);
+        assert_eq!(fri_optimal_queries(3, 8, 80), 27);
+        assert_eq!(fri_optimal_queries(3, 2, 0), 0);
+    }
+
+    #[test]
+    pub fn security_level() {
+        // The blowup factor of this crate's domains
+        assert_eq!(SecurityLevel::Bits80.queries_needed(17, 3, 2), 80);
+        // GF(17) allows a blowup factor of 4 for a degree 3 polynomial
+        assert_eq!(SecurityLevel::Bits80.queries_needed(17, 3, 4), 40);
+        assert_eq!(SecurityLevel::Bits128.queries_needed(17, 3, 4), 64);
+        // ... but only of 2 for a degree 7 polynomial
+        assert_eq!(SecurityLevel::Bits128.queries_needed(17, 7, 2), 128);
+
+        // The Goldilocks field (2^64 - 2^32 + 1) is just short of 64 bits
+        let goldilocks = u64::MAX - (1 << 32) + 2;
+        for level in [
+            SecurityLevel::Bits64,
+            SecurityLevel::Bits80,
+            SecurityLevel::Bits100,
+            SecurityLevel::Bits128,
+        ] {
+            assert!(!level.is_achievable_in_field(17));
+            assert!(!level.is_achievable_in_field(goldilocks));
+            assert!(!level.is_achievable_in_field(0));
+        }
+        assert_eq!(SecurityLevel::Bits100.bits(), 100);
+    }
+
+    #[test]
+    #[should_panic]
+    pub fn queries_needed_domain_too_large() {
+        // GF(17) can't hold 8 * 4 elements
+        SecurityLevel::Bits128.queries_needed(17, 7, 4);
+    }
+
+    #[test]
+    #[should_panic]
+    pub fn queries_needed_field_too_small() {
+        SecurityLevel::Bits128.queries_needed(1, 3, 2);
+    }
+}
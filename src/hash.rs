@@ -0,0 +1,76 @@
+use blake3::Hash;
+use sha2::{Digest, Sha256};
+
+/// The hash function used by the `Channel` and the Merkle trees.
+///
+/// Both functions have 32-byte outputs, which we store in a `blake3::Hash`
+/// regardless of the function that produced them (it is only used as a
+/// container, with constant-time equality).
+///
+/// With the `serde` feature, this is serialized as a unit variant (`Blake3` is
+/// variant 0, and `Sha256` is variant 1).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum HashAlgorithm {
+    #[default]
+    Blake3,
+    /// Ethereum smart contracts can only afford SHA-256 (through a
+    /// precompile), so this is what STARKs verified on the EVM use.
+    Sha256,
+}
+
+impl HashAlgorithm {
+    /// Hashes `data`
+    pub fn hash(self, data: &[u8]) -> Hash {
+        self.hash_all(&[data])
+    }
+
+    /// Hashes the concatenation of every element of `chunks`
+    pub fn hash_all(self, chunks: &[&[u8]]) -> Hash {
+        match self {
+            HashAlgorithm::Blake3 => {
+                let mut hasher = blake3::Hasher::new();
+                for chunk in chunks {
+                    hasher.update(chunk);
+                }
+
+                hasher.finalize()
+            }
+            HashAlgorithm::Sha256 => {
+                let mut hasher = Sha256::new();
+                for chunk in chunks {
+                    hasher.update(chunk);
+                }
+
+                Hash::from(<[u8; 32]>::from(hasher.finalize()))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn hash_all_is_hash_of_concatenation() {
+        for algorithm in [HashAlgorithm::Blake3, HashAlgorithm::Sha256] {
+            assert_eq!(
+                algorithm.hash_all(&[b"stark", b"-102"]),
+                algorithm.hash(b"stark-102")
+            );
+        }
+
+        assert_eq!(HashAlgorithm::Blake3.hash(b"abc"), blake3::hash(b"abc"));
+        assert_ne!(
+            HashAlgorithm::Blake3.hash(b"abc"),
+            HashAlgorithm::Sha256.hash(b"abc")
+        );
+
+        // Test vector from FIPS 180-2
+        assert_eq!(
+            HashAlgorithm::Sha256.hash(b"abc").to_hex().as_str(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+}
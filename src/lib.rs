@@ -1,58 +1,366 @@
-#![feature(slice_as_chunks)]
-
 pub mod channel;
 pub mod constraints;
 pub mod domain;
+pub mod extension;
 pub mod field;
+pub mod fri;
+pub mod hash;
 pub mod merkle;
+pub mod ntt;
 pub mod poly;
 pub(crate) mod prover;
 pub mod trace;
 pub mod util;
-pub(crate) mod verifier;
+pub mod verifier;
 
+use anyhow::{bail, Result};
 use field::BaseField;
-use merkle::{MerklePath, MerkleRoot};
+use fri::{FriCommitment, FriProof};
+use hash::HashAlgorithm;
+use merkle::{MerklePath, MerkleRoot, SiblingPosition};
 
 /// Generate the STARK
-pub use prover::generate_proof;
+pub use prover::{
+    estimate_prover_work, generate_proof, generate_proof_verified, generate_proof_with_config,
+    generate_proof_with_options, generate_proof_with_transcript, ProverConfig, ProverError,
+    ProverWorkEstimate, MAX_GRINDING_BITS,
+};
 
 /// Verify the STARK
-pub use verifier::verify;
+pub use verifier::{verify, verify_with_config, VerificationError, VerifierConfig};
 
-#[derive(Clone, Debug)]
+/// With the `serde` feature, the fields are serialized in declaration order.
+/// Merkle roots are serialized as their 32 bytes (see `merkle::serde_hash`).
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct StarkProof {
+    /// The hash function used for the Merkle trees and the channel. See
+    /// `ProverConfig::hash_algorithm`.
+    pub hash_algorithm: HashAlgorithm,
+
     // Commitment phase
+    #[cfg_attr(feature = "serde", serde(with = "merkle::serde_hash"))]
     pub trace_lde_commitment: MerkleRoot,
 
     // The composition polynomial has degree 3 (it was *interpolated* on 4
     // points, and *evaluated* on 8).
+    #[cfg_attr(feature = "serde", serde(with = "merkle::serde_hash"))]
     pub composition_poly_lde_commitment: MerkleRoot,
 
-    // The first FRI layer has half the degree of the composition polynomial
-    // (i.e. degree 1)
-    pub fri_layer_deg_1_commitment: MerkleRoot,
+    /// The FRI proof that the composition polynomial has degree at most 3.
+    /// Its first layer is the composition polynomial, opened at each query.
+    pub fri: FriProof,
+
+    /// The nonce found by `Channel::grind()`; `None` when
+    /// `ProverConfig::grinding_bits` is 0. The number of bits is not part of
+    /// the proof: the verifier requires its own (see
+    /// `VerifierConfig::grinding_bits`).
+    pub grind_nonce: Option<u64>,
+
+    /// One entry per query. See `ProverConfig::num_queries`.
+    pub query_phase: Vec<ProofQueryPhase>,
+}
+
+impl StarkProof {
+    /// Counts the number of `BaseField` elements in the proof (i.e. the
+    /// queried values).
+    pub fn num_field_elements(&self) -> usize {
+        // trace(x) and trace(gx) for each query, every value opened by FRI, and
+        // the value of the last FRI layer
+        2 * self.query_phase.len() + self.fri.query_paths.iter().map(Vec::len).sum::<usize>() + 1
+    }
+
+    /// Counts the number of bytes taken by hashes in the proof; that is, the
+    /// Merkle roots, and the hashes in every Merkle path.
+    pub fn num_hash_bytes(&self) -> usize {
+        let num_roots = 2 + self.fri.commitment.layer_roots.len();
+
+        let trace_paths = self
+            .query_phase
+            .iter()
+            .flat_map(|query| [&query.trace_x.1, &query.trace_gx.1]);
+        let fri_paths = self
+            .fri
+            .query_paths
+            .iter()
+            .flatten()
+            .map(|(_, merkle_path)| merkle_path);
+
+        let num_path_hashes = trace_paths
+            .chain(fri_paths)
+            .map(|merkle_path| merkle_path.len())
+            .sum::<usize>();
+
+        (num_roots + num_path_hashes) * blake3::OUT_LEN
+    }
+
+    /// The total size of the proof in bytes, where each `BaseField` element
+    /// takes 1 byte.
+    pub fn total_proof_size_bytes(&self) -> usize {
+        self.num_field_elements() + self.num_hash_bytes()
+    }
+}
+
+impl StarkProof {
+    /// Encodes the proof in a deterministic format, decoded by
+    /// `StarkProof::from_bytes()`:
+    ///
+    /// 1. The hash algorithm, as a single byte (0 for `Blake3`, 1 for `Sha256`)
+    /// 2. The trace and composition polynomial Merkle roots (32 bytes each)
+    /// 3. The number of FRI layer roots (1 byte), followed by the roots, and the
+    ///    value of the last FRI layer as a single byte
+    /// 4. The grind nonce: a 0 byte if there is none, or a 1 byte followed by
+    ///    the nonce as a little-endian `u64`
+    /// 5. The number of queries, as a little-endian `u32`
+    /// 6. For each query, the Merkle paths of `trace_x` and `trace_gx`, the
+    ///    number of values opened by FRI (1 byte) followed by their Merkle
+    ///    paths, and finally all of the query's values as single bytes (`trace_x`
+    ///    and `trace_gx`, then the FRI values)
+    ///
+    /// Each Merkle path is encoded as its length (1 byte), followed by the `(hash,
+    /// position)` tuples starting from the leaf, where the hash takes 32 bytes and
+    /// the position 1 byte (0 for `Left`, 1 for `Right`).
+    ///
+    /// Fails if the proof can't be represented in this format: if it doesn't
+    /// have as many FRI query paths as queries, or if a count doesn't fit in
+    /// the number of bytes allotted to it.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        if self.query_phase.len() != self.fri.query_paths.len() {
+            bail!(
+                "{} queries but {} FRI query paths",
+                self.query_phase.len(),
+                self.fri.query_paths.len()
+            );
+        }
+
+        let mut bytes = Vec::new();
+
+        bytes.push(match self.hash_algorithm {
+            HashAlgorithm::Blake3 => 0,
+            HashAlgorithm::Sha256 => 1,
+        });
+
+        bytes.extend_from_slice(self.trace_lde_commitment.as_bytes());
+        bytes.extend_from_slice(self.composition_poly_lde_commitment.as_bytes());
+
+        bytes.push(encode_len(self.fri.commitment.layer_roots.len())?);
+        for layer_root in self.fri.commitment.layer_roots.iter() {
+            bytes.extend_from_slice(layer_root.as_bytes());
+        }
+        bytes.push(self.fri.commitment.final_value.as_byte());
+
+        match self.grind_nonce {
+            Some(nonce) => {
+                bytes.push(1);
+                bytes.extend_from_slice(&nonce.to_le_bytes());
+            }
+            None => bytes.push(0),
+        }
+
+        let num_queries = u32::try_from(self.query_phase.len())?;
+        bytes.extend_from_slice(&num_queries.to_le_bytes());
+
+        for (query, fri_openings) in self.query_phase.iter().zip(self.fri.query_paths.iter()) {
+            write_merkle_path(&mut bytes, &query.trace_x.1)?;
+            write_merkle_path(&mut bytes, &query.trace_gx.1)?;
+
+            bytes.push(encode_len(fri_openings.len())?);
+            for (_, merkle_path) in fri_openings.iter() {
+                write_merkle_path(&mut bytes, merkle_path)?;
+            }
+
+            bytes.extend_from_slice(&[query.trace_x.0.as_byte(), query.trace_gx.0.as_byte()]);
+            bytes.extend(fri_openings.iter().map(|(value, _)| value.as_byte()));
+        }
+
+        Ok(bytes)
+    }
+
+    /// Decodes a proof encoded with `StarkProof::to_bytes()`
+    pub fn from_bytes(bytes: &[u8]) -> Result<StarkProof> {
+        let mut reader = ByteReader { bytes };
+
+        let hash_algorithm = match reader.read_array()? {
+            [0] => HashAlgorithm::Blake3,
+            [1] => HashAlgorithm::Sha256,
+            [algorithm] => bail!("invalid hash algorithm {algorithm}"),
+        };
+
+        let trace_lde_commitment = reader.read_hash()?;
+        let composition_poly_lde_commitment = reader.read_hash()?;
+
+        let [num_layer_roots] = reader.read_array()?;
+        let layer_roots = (0..num_layer_roots)
+            .map(|_| reader.read_hash())
+            .collect::<Result<Vec<_>>>()?;
+        let final_value = reader.read_field_element()?;
+
+        let grind_nonce = match reader.read_array()? {
+            [0] => None,
+            [1] => Some(u64::from_le_bytes(reader.read_array()?)),
+            [flag] => bail!("invalid grind nonce flag {flag}"),
+        };
+
+        let num_queries = u32::from_le_bytes(reader.read_array()?);
+
+        let (query_phase, query_paths) = (0..num_queries)
+            .map(|_| {
+                let trace_x_path = reader.read_merkle_path()?;
+                let trace_gx_path = reader.read_merkle_path()?;
+
+                let [num_fri_openings] = reader.read_array()?;
+                let fri_paths = (0..num_fri_openings)
+                    .map(|_| reader.read_merkle_path())
+                    .collect::<Result<Vec<_>>>()?;
+
+                let trace_x = reader.read_field_element()?;
+                let trace_gx = reader.read_field_element()?;
+                let fri_openings = fri_paths
+                    .into_iter()
+                    .map(|merkle_path| Ok((reader.read_field_element()?, merkle_path)))
+                    .collect::<Result<Vec<_>>>()?;
+
+                let query = ProofQueryPhase {
+                    trace_x: (trace_x, trace_x_path),
+                    trace_gx: (trace_gx, trace_gx_path),
+                };
+
+                Ok((query, fri_openings))
+            })
+            .collect::<Result<(Vec<_>, Vec<_>)>>()?;
+
+        if !reader.bytes.is_empty() {
+            bail!("{} trailing bytes after proof", reader.bytes.len());
+        }
+
+        Ok(StarkProof {
+            hash_algorithm,
+            trace_lde_commitment,
+            composition_poly_lde_commitment,
+            fri: FriProof {
+                commitment: FriCommitment {
+                    layer_roots,
+                    final_value,
+                },
+                query_paths,
+            },
+            grind_nonce,
+            query_phase,
+        })
+    }
 
-    pub query_phase: ProofQueryPhase,
+    /// Normalizes the representation of the proof.
+    ///
+    /// There turns out to be very little to normalize:
+    /// - `BaseField` elements are always reduced modulo 17, and hashes have a
+    ///   fixed size.
+    /// - The proof contains no polynomials (only evaluations).
+    /// - The queries can't be sorted, since the verifier matches them with the
+    ///   query indices in the order it draws them from the channel.
+    ///
+    /// Hence, this simply normalizes the proof to what its canonical encoding
+    /// (see `StarkProof::to_bytes()`) decodes to. Fails, leaving the proof
+    /// untouched, if the proof can't be encoded.
+    pub fn canonicalize(&mut self) -> Result<()> {
+        *self = Self::from_bytes(&self.to_bytes()?)?;
+
+        Ok(())
+    }
+}
+
+/// Appends the encoding of `merkle_path` described in `StarkProof::to_bytes()`
+/// to `bytes`
+fn write_merkle_path(bytes: &mut Vec<u8>, merkle_path: &MerklePath) -> Result<()> {
+    bytes.push(encode_len(merkle_path.len())?);
+
+    for (sibling_hash, sibling_position) in merkle_path.path.iter() {
+        bytes.extend_from_slice(sibling_hash.as_bytes());
+        bytes.push(match sibling_position {
+            SiblingPosition::Left => 0,
+            SiblingPosition::Right => 1,
+        });
+    }
+
+    Ok(())
 }
 
-/// Our STARK proof only supports one query. However, in production systems, we
-/// want to do more than one query to increase the security of the system.
-#[derive(Clone, Debug)]
+/// Encodes a length on the single byte that `StarkProof::to_bytes()` allots
+/// to it
+fn encode_len(len: usize) -> Result<u8> {
+    match u8::try_from(len) {
+        Ok(len) => Ok(len),
+        Err(_) => bail!("length {len} doesn't fit in a byte"),
+    }
+}
+
+/// Reads the encoding described in `StarkProof::to_bytes()`, consuming the
+/// bytes as they are read.
+struct ByteReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl ByteReader<'_> {
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        if self.bytes.len() < N {
+            bail!(
+                "unexpected end of proof: expected {N} bytes, got {}",
+                self.bytes.len()
+            );
+        }
+
+        let (array, rest) = self.bytes.split_at(N);
+        self.bytes = rest;
+
+        Ok(array.try_into().unwrap())
+    }
+
+    fn read_hash(&mut self) -> Result<MerkleRoot> {
+        Ok(MerkleRoot::from(self.read_array::<{ blake3::OUT_LEN }>()?))
+    }
+
+    fn read_field_element(&mut self) -> Result<BaseField> {
+        let [byte] = self.read_array()?;
+        if byte >= 17 {
+            bail!("invalid field element {byte}");
+        }
+
+        Ok(BaseField::from(byte))
+    }
+
+    fn read_merkle_path(&mut self) -> Result<MerklePath> {
+        let [path_len] = self.read_array()?;
+
+        let path = (0..path_len)
+            .map(|_| {
+                let sibling_hash = self.read_hash()?;
+                let sibling_position = match self.read_array()? {
+                    [0] => SiblingPosition::Left,
+                    [1] => SiblingPosition::Right,
+                    [position] => bail!("invalid sibling position {position}"),
+                };
+
+                Ok((sibling_hash, sibling_position))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(MerklePath { path })
+    }
+}
+
+/// The trace values (and Merkle proofs) sent by the prover for a single query.
+/// A proof contains one of these per query, along with the corresponding entry
+/// of `FriProof::query_paths`; each additional query decreases the probability
+/// that a cheating prover convinces the verifier.
+///
+/// With the `serde` feature, the fields are serialized in declaration order,
+/// each as a `(value, merkle_path)` tuple.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ProofQueryPhase {
     pub trace_x: (BaseField, MerklePath),
 
     // trace(gx); where g is the generator for the original domain (size 4)
     pub trace_gx: (BaseField, MerklePath),
-
-    // `composition_polynomial(-x)` (degree 3)
-    pub cp_minus_x: (BaseField, MerklePath),
-
-    // fri_layer_deg_1_eval(-x^2)
-    pub fri_layer_deg_1_minus_x: (BaseField, MerklePath),
-
-    // fri_layer_deg_0_eval(x^4)
-    pub fri_layer_deg_0_x: BaseField,
 }
 
 #[cfg(test)]
@@ -61,9 +369,188 @@ mod tests {
 
     #[test]
     pub fn proof_verification() {
-        let proof = generate_proof();
-        let verify_result = verify(&proof);
+        let proof = generate_proof(&[]);
+        let verify_result = verify(&proof, &[]);
 
         assert!(verify_result.is_ok(), "Error: {verify_result:?}");
     }
+
+    #[test]
+    pub fn proof_bound_to_pub_inputs() {
+        let proof_1 = generate_proof(b"statement 1");
+        let proof_2 = generate_proof(b"statement 2");
+
+        // The trace doesn't depend on the public inputs, but everything
+        // derived from the channel's randomness does
+        assert_eq!(proof_1.trace_lde_commitment, proof_2.trace_lde_commitment);
+        assert_ne!(
+            proof_1.composition_poly_lde_commitment,
+            proof_2.composition_poly_lde_commitment
+        );
+
+        assert!(verify(&proof_1, b"statement 1").is_ok());
+        assert!(verify(&proof_2, b"statement 2").is_ok());
+        assert!(verify(&proof_1, b"statement 2").is_err());
+        assert!(verify(&proof_2, b"statement 1").is_err());
+    }
+
+    #[test]
+    pub fn proof_verification_for_each_hash_algorithm() {
+        for hash_algorithm in [HashAlgorithm::Blake3, HashAlgorithm::Sha256] {
+            let proof = generate_proof_with_config(
+                &[],
+                &ProverConfig {
+                    hash_algorithm,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+            assert_eq!(proof.hash_algorithm, hash_algorithm);
+
+            let verify_result = verify(&proof, &[]);
+            assert!(
+                verify_result.is_ok(),
+                "{hash_algorithm:?}: {verify_result:?}"
+            );
+
+            // The proof doesn't verify with the other hash algorithm
+            let mut tampered_proof = proof.clone();
+            tampered_proof.hash_algorithm = match hash_algorithm {
+                HashAlgorithm::Blake3 => HashAlgorithm::Sha256,
+                HashAlgorithm::Sha256 => HashAlgorithm::Blake3,
+            };
+            assert!(verify(&tampered_proof, &[]).is_err());
+
+            let decoded_proof = StarkProof::from_bytes(&proof.to_bytes().unwrap()).unwrap();
+            assert_eq!(decoded_proof.hash_algorithm, hash_algorithm);
+        }
+    }
+
+    #[test]
+    pub fn proof_size() {
+        let proof = generate_proof(&[]);
+
+        // trace(x), trace(gx), cp(x), cp(-x), fri_layer_deg_1(-x^2), and the
+        // last FRI layer's value
+        assert_eq!(proof.num_field_elements(), 6);
+
+        // 3 roots, 3 + 3 hashes for the trace paths (8 leaves), 3 + 3 for the
+        // composition polynomial paths (8 leaves), and 2 for the FRI layer path
+        // (4 leaves)
+        assert_eq!(proof.num_hash_bytes(), (3 + 3 + 3 + 3 + 3 + 2) * 32);
+
+        assert_eq!(proof.total_proof_size_bytes(), 6 + 17 * 32);
+
+        let proof = generate_proof_with_config(
+            &[],
+            &ProverConfig {
+                num_queries: 3,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(proof.num_field_elements(), 3 * 5 + 1);
+        assert_eq!(proof.num_hash_bytes(), (3 + 3 * 14) * 32);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    pub fn serde_round_trip() {
+        let proof = generate_proof_with_config(
+            &[],
+            &ProverConfig {
+                num_queries: 3,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let bytes = bincode::serialize(&proof).unwrap();
+        let proof2: StarkProof = bincode::deserialize(&bytes).unwrap();
+
+        assert!(verify_with_config(
+            &proof2,
+            &[],
+            &VerifierConfig {
+                num_queries: 3,
+                ..Default::default()
+            }
+        )
+        .is_ok());
+        assert_eq!(bincode::serialize(&proof2).unwrap(), bytes);
+    }
+
+    #[test]
+    pub fn bytes_round_trip() {
+        let proof = generate_proof_with_config(
+            &[],
+            &ProverConfig {
+                num_queries: 3,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let bytes = proof.to_bytes().unwrap();
+        assert_eq!(
+            bytes.len(),
+            proof.total_proof_size_bytes() + 1 + 1 + 1 + 4 + 3 * 6 + 3 * 14
+        );
+
+        let proof2 = StarkProof::from_bytes(&bytes).unwrap();
+        assert_eq!(proof2.to_bytes().unwrap(), bytes);
+        assert_eq!(proof2, proof);
+        assert!(verify_with_config(
+            &proof2,
+            &[],
+            &VerifierConfig {
+                num_queries: 3,
+                ..Default::default()
+            }
+        )
+        .is_ok());
+
+        assert!(StarkProof::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(StarkProof::from_bytes(&[bytes.as_slice(), &[0]].concat()).is_err());
+
+        // Proofs that can't be encoded
+        let mut mismatched_proof = proof.clone();
+        mismatched_proof.fri.query_paths.pop();
+        assert!(mismatched_proof.to_bytes().is_err());
+        assert!(mismatched_proof.clone().canonicalize().is_err());
+
+        let mut too_many_layers_proof = proof;
+        too_many_layers_proof.fri.commitment.layer_roots =
+            vec![too_many_layers_proof.trace_lde_commitment; 256];
+        assert!(too_many_layers_proof.to_bytes().is_err());
+    }
+
+    #[test]
+    pub fn canonicalize_idempotent() {
+        let mut proof = generate_proof_with_config(
+            &[],
+            &ProverConfig {
+                num_queries: 3,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let original_bytes = proof.to_bytes().unwrap();
+
+        proof.canonicalize().unwrap();
+        let canonical_bytes = proof.to_bytes().unwrap();
+        assert_eq!(canonical_bytes, original_bytes);
+        assert!(verify_with_config(
+            &proof,
+            &[],
+            &VerifierConfig {
+                num_queries: 3,
+                ..Default::default()
+            }
+        )
+        .is_ok());
+
+        proof.canonicalize().unwrap();
+        assert_eq!(proof.to_bytes().unwrap(), canonical_bytes);
+    }
 }
@@ -1,258 +1,609 @@
-use std::{cell::RefCell, rc::Rc};
-
-use anyhow::{anyhow, bail, Result};
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    ops::Range,
+    path::Path,
+};
+
+use anyhow::{bail, Result};
 use blake3::Hash;
 
-use crate::{field::BaseField, util::is_power_of_2};
+use crate::{field::BaseField, hash::HashAlgorithm, util::is_power_of_2};
 
 pub type MerkleRoot = blake3::Hash;
 
 /// Identifies whether a hash corresponds to the left or right sibling.
 /// This is necessary in order to properly verify an inclusion proof
+///
+/// With the `serde` feature, this is serialized as a unit variant (`Left` is
+/// variant 0, and `Right` is variant 1).
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SiblingPosition {
     Left,
     Right,
 }
 
+/// With the `serde` feature, this is serialized as a sequence of `(hash,
+/// position)` tuples, starting from the leaf. Each hash is serialized as its 32
+/// bytes (see `serde_hash`).
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MerklePath {
     /// Hashes starting from the leaf to right below the root (<hash>, Left)
     /// means that our sibling has hash <hash>, and is the left child of our
     /// parent (such that we are the right child)
+    #[cfg_attr(feature = "serde", serde(with = "serde_path"))]
     pub path: Vec<(Hash, SiblingPosition)>,
 }
 
+impl PartialEq for MerklePath {
+    fn eq(&self, other: &Self) -> bool {
+        self.path.len() == other.path.len()
+            && self
+                .path
+                .iter()
+                .zip(other.path.iter())
+                .all(|(lhs, rhs)| lhs == rhs)
+    }
+}
+
+impl Eq for MerklePath {}
+
 impl MerklePath {
     pub fn new(merkle_tree: &MerkleTree, index: usize) -> Result<Self> {
-        if index >= merkle_tree.leaves.len() {
+        let Some(mut node) = merkle_tree.leaf(index) else {
             bail!(
                 "index {index} out of bounds ({} leaves)",
-                merkle_tree.leaves.len()
+                merkle_tree.num_leaves()
             );
-        }
+        };
 
         let mut path = Vec::new();
-        let mut node_runner = merkle_tree.leaves[index].clone();
+        while let Some((sibling, sibling_position)) = node.sibling() {
+            path.push((sibling.hash(), sibling_position));
 
-        while node_runner.borrow().parent().is_some() {
-            let current_node = Rc::clone(&node_runner);
-            let (maybe_current_node_sibling, sibling_position) = current_node
-                .borrow()
-                .sibling()
-                .ok_or(anyhow!("sibling doesn't exist"))?;
+            node = node.parent().expect("a node with a sibling has a parent");
+        }
 
-            let current_node_sibling = maybe_current_node_sibling.unwrap();
+        Ok(Self { path })
+    }
 
-            path.push((current_node_sibling.borrow().hash(), sibling_position));
+    /// Returns the sibling hash at `depth`, where depth 0 is the sibling of the
+    /// leaf, and depth `path.len() - 1` is the child of the root.
+    pub fn sibling_hash_at_depth(&self, depth: usize) -> Option<(Hash, &SiblingPosition)> {
+        self.path
+            .get(depth)
+            .map(|(sibling_hash, sibling_position)| (*sibling_hash, sibling_position))
+    }
 
-            node_runner = current_node.borrow().parent().unwrap();
-        }
+    /// Returns the index of the leaf that the path starts from. At each depth,
+    /// a sibling on the left means that the current node is a right child,
+    /// which sets the corresponding bit of the index.
+    ///
+    /// Precondition: the path has fewer than `usize::BITS` hashes
+    pub fn leaf_index(&self) -> usize {
+        self.path
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, sibling_position))| *sibling_position == SiblingPosition::Left)
+            .map(|(depth, _)| 1 << depth)
+            .sum()
+    }
 
-        Ok(Self { path })
+    /// Returns the number of hashes in the path, which is the depth of the
+    /// tree (i.e. `log2` of the number of leaves).
+    pub fn len(&self) -> usize {
+        self.path.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.path.is_empty()
     }
 
     pub fn verify_inclusion(&self, element: BaseField, root: MerkleRoot) -> bool {
-        let mut current_hash = blake3::hash(&[element.as_byte()]);
+        self.verify_inclusion_with_hash(element, root, HashAlgorithm::default())
+    }
+
+    /// Same as `verify_inclusion()`, for a tree built with `hash_algorithm`
+    /// (see `MerkleTree::new_with_hash()`).
+    pub fn verify_inclusion_with_hash(
+        &self,
+        element: BaseField,
+        root: MerkleRoot,
+        hash_algorithm: HashAlgorithm,
+    ) -> bool {
+        self.recompute_root_with_hash(element, hash_algorithm) == root
+    }
+
+    /// Recomputes the root of the tree from the claimed value of the leaf, and
+    /// the sibling hashes in the path. The full `MerkleTree` is not needed.
+    pub fn recompute_root(&self, element: BaseField) -> MerkleRoot {
+        self.recompute_root_with_hash(element, HashAlgorithm::default())
+    }
+
+    /// Same as `recompute_root()`, for a tree built with `hash_algorithm`
+    pub fn recompute_root_with_hash(
+        &self,
+        element: BaseField,
+        hash_algorithm: HashAlgorithm,
+    ) -> MerkleRoot {
+        let mut current_hash = hash_algorithm.hash(&[element.as_byte()]);
 
         for (sibling_hash, sibling_position) in &self.path {
             current_hash = match sibling_position {
                 SiblingPosition::Left => {
                     // sibling hash comes first
-                    let mut hasher = blake3::Hasher::new();
-                    hasher.update(sibling_hash.as_bytes());
-                    hasher.update(current_hash.as_bytes());
-                    hasher.finalize()
+                    hash_algorithm.hash_all(&[sibling_hash.as_bytes(), current_hash.as_bytes()])
                 }
                 SiblingPosition::Right => {
                     // sibling hash comes second
-                    let mut hasher = blake3::Hasher::new();
-                    hasher.update(current_hash.as_bytes());
-                    hasher.update(sibling_hash.as_bytes());
-                    hasher.finalize()
+                    hash_algorithm.hash_all(&[current_hash.as_bytes(), sibling_hash.as_bytes()])
                 }
             }
         }
 
-        root == current_hash
+        current_hash
     }
 }
 
-/// A Merkle tree implementation that uses blake3 as a hashing function
+/// The Merkle paths of several leaves of the same tree (see
+/// `MerkleTree::batch_proof()`). This is what a multi-query STARK opens
+/// against each commitment.
+///
+/// Note: the paths are stored independently, so nodes shared by several paths
+/// are repeated. Sending them only once would make the batch smaller.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MerkleBatchPath {
+    pub paths: Vec<MerklePath>,
+}
+
+impl MerkleBatchPath {
+    /// Returns `true` if `elements[i]` is included in the tree with root
+    /// `root`, according to `paths[i]`, for every `i`.
+    pub fn verify_batch(&self, elements: &[BaseField], root: MerkleRoot) -> bool {
+        self.verify_batch_with_hash(elements, root, HashAlgorithm::default())
+    }
+
+    /// Same as `verify_batch()`, for a tree built with `hash_algorithm` (see
+    /// `MerkleTree::new_with_hash()`).
+    pub fn verify_batch_with_hash(
+        &self,
+        elements: &[BaseField],
+        root: MerkleRoot,
+        hash_algorithm: HashAlgorithm,
+    ) -> bool {
+        elements.len() == self.paths.len()
+            && self.paths.iter().zip(elements).all(|(path, element)| {
+                path.verify_inclusion_with_hash(*element, root, hash_algorithm)
+            })
+    }
+}
+
+/// Computes the root of the Merkle tree of `leaf_values` (i.e. the same as
+/// `MerkleTree::new(leaf_values).root`), without building the tree. We only
+/// keep the hashes of the current layer in a flat buffer, which we halve until
+/// only the root remains.
+pub fn root_only(leaf_values: &[BaseField]) -> MerkleRoot {
+    root_only_with_hash(leaf_values, HashAlgorithm::default())
+}
+
+/// Same as `root_only()`, but hashes the nodes with `hash_algorithm`
+pub fn root_only_with_hash(leaf_values: &[BaseField], hash_algorithm: HashAlgorithm) -> MerkleRoot {
+    if !is_power_of_2(leaf_values.len()) {
+        panic!("Merkle tree expects leaves to be power of 2")
+    }
+
+    let mut current_layer: Vec<Hash> = leaf_values
+        .iter()
+        .map(|ele| hash_algorithm.hash(&[ele.as_byte()]))
+        .collect();
+
+    while current_layer.len() > 1 {
+        current_layer = current_layer
+            .as_chunks::<2>()
+            .0
+            .iter()
+            .map(|[left, right]| hash_algorithm.hash_all(&[left.as_bytes(), right.as_bytes()]))
+            .collect();
+    }
+
+    current_layer[0]
+}
+
+/// A Merkle tree implementation that uses blake3 as a hashing function by
+/// default (see `MerkleTree::new_with_hash()`).
+///
+/// The nodes are stored in a flat vector, indexed as a binary heap: the root
+/// is at index 0, and the children of the node at index `i` are at indices `2i
+/// + 1` and `2i + 2`. Hence, for `n` leaves, the leaves are at indices `n - 1`
+/// to `2n - 2`, and navigating the tree is only arithmetic on indices.
+#[derive(Clone, Debug)]
 pub struct MerkleTree {
-    pub leaves: Vec<Rc<RefCell<Node>>>,
+    nodes: Vec<Hash>,
+    hash_algorithm: HashAlgorithm,
     pub root: Hash,
 }
 
 impl MerkleTree {
     pub fn new(leaf_values: &[BaseField]) -> Self {
-        if !is_power_of_2(leaf_values.len()) {
-            panic!("Merkle tree expects leaves to be power of 2")
-        }
+        Self::new_with_hash(leaf_values, HashAlgorithm::default())
+    }
 
-        let leaves: Vec<Rc<RefCell<Node>>> = leaf_values
+    /// Same as `new()`, but hashes the nodes with `hash_algorithm`
+    pub fn new_with_hash(leaf_values: &[BaseField], hash_algorithm: HashAlgorithm) -> Self {
+        let leaf_hashes: Vec<Hash> = leaf_values
             .iter()
             .map(|ele| {
-                let leaf_hash = {
-                    let leaf_bytes: [u8; 1] = [ele.as_byte()];
-                    blake3::hash(&leaf_bytes)
-                };
-
-                Rc::new(RefCell::new(Node::Leaf(LeafNode {
-                    parent: None,
-                    hash: leaf_hash,
-                })))
+                let leaf_bytes: [u8; 1] = [ele.as_byte()];
+                hash_algorithm.hash(&leaf_bytes)
             })
             .collect();
 
-        let mut current_layer: Vec<Rc<RefCell<Node>>> = leaves.to_vec();
-
-        while current_layer.len() > 1 {
-            current_layer = current_layer
-                .as_chunks_mut::<2>()
-                .0
-                .iter_mut()
-                .map(|[left, right]| {
-                    let hash = {
-                        let mut hasher = blake3::Hasher::new();
-                        hasher.update(left.borrow().hash().as_bytes());
-                        hasher.update(right.borrow().hash().as_bytes());
-                        hasher.finalize()
-                    };
-
-                    let internal_node = Rc::new(RefCell::new(Node::Internal(InternalNode {
-                        left: Some(left.clone()),
-                        right: Some(right.clone()),
-                        parent: None,
-                        hash,
-                    })));
-
-                    left.borrow_mut().set_parent(internal_node.clone());
-                    right.borrow_mut().set_parent(internal_node.clone());
-
-                    internal_node
-                })
-                .collect();
+        Self::from_leaf_hashes_with_hash(&leaf_hashes, hash_algorithm)
+    }
+
+    /// Builds the tree from the hashes of the leaves, computed by the caller.
+    /// This is useful when a leaf is not a single `BaseField` element (e.g. a
+    /// row of several columns).
+    pub fn from_leaf_hashes(leaf_hashes: &[Hash]) -> Self {
+        Self::from_leaf_hashes_with_hash(leaf_hashes, HashAlgorithm::default())
+    }
+
+    /// Same as `from_leaf_hashes()`, but hashes the internal nodes with
+    /// `hash_algorithm`
+    pub fn from_leaf_hashes_with_hash(leaf_hashes: &[Hash], hash_algorithm: HashAlgorithm) -> Self {
+        if !is_power_of_2(leaf_hashes.len()) {
+            panic!("Merkle tree expects leaves to be power of 2")
         }
 
-        let root_node = current_layer[0].borrow();
+        let num_internal_nodes = leaf_hashes.len() - 1;
+
+        // The internal nodes are placeholders until we compute them below
+        let mut nodes = vec![Hash::from([0u8; blake3::OUT_LEN]); num_internal_nodes];
+        nodes.extend_from_slice(leaf_hashes);
+
+        // Children always come after their parent, so going backwards computes
+        // both children before their parent.
+        for i in (0..num_internal_nodes).rev() {
+            nodes[i] = hash_algorithm
+                .hash_all(&[nodes[2 * i + 1].as_bytes(), nodes[2 * i + 2].as_bytes()]);
+        }
 
         Self {
-            leaves,
-            root: root_node.hash(),
+            root: nodes[0],
+            nodes,
+            hash_algorithm,
         }
     }
-}
 
-#[derive(Debug, PartialEq, Eq)]
-pub enum Node {
-    Internal(InternalNode),
-    Leaf(LeafNode),
-}
+    /// Replaces the leaf at `index` with `new_value`, and recomputes the
+    /// internal nodes on the path to the root. Fails if `index` is out of
+    /// bounds.
+    pub fn update_leaf(&mut self, index: usize, new_value: BaseField) -> Result<()> {
+        if index >= self.num_leaves() {
+            bail!("index {index} out of bounds ({} leaves)", self.num_leaves());
+        }
 
-impl Node {
-    /// Only the root node will return `None`
-    pub fn parent(&self) -> Option<Rc<RefCell<Node>>> {
-        match self {
-            Node::Internal(node) => node.parent.as_ref().cloned(),
-            Node::Leaf(node) => node.parent.as_ref().cloned(),
+        let mut node_index = self.num_leaves() - 1 + index;
+        self.nodes[node_index] = self.hash_algorithm.hash(&[new_value.as_byte()]);
+
+        while node_index > 0 {
+            node_index = (node_index - 1) / 2;
+            self.nodes[node_index] = self.hash_children(node_index);
         }
+        self.root = self.nodes[0];
+
+        Ok(())
     }
 
-    /// Only leaf nodes will return `None`
+    /// Recomputes the hash of every internal node from its children, and
+    /// checks that it matches the stored hash (and that the root matches the
+    /// root node). A tree built with one of the constructors always passes
+    /// this check.
+    pub fn verify_all(&self) -> bool {
+        let num_internal_nodes = self.num_leaves() - 1;
+
+        self.root == self.nodes[0]
+            && (0..num_internal_nodes)
+                .rev()
+                .all(|index| self.nodes[index] == self.hash_children(index))
+    }
+
+    /// Writes the tree to `w`, in the following format:
+    /// 1. The hash algorithm, as a single byte (0 for `Blake3`, 1 for `Sha256`)
+    /// 2. The number of leaves, as a little-endian `u32`
+    /// 3. The hash of each leaf (32 bytes), from left to right
     ///
-    /// Note: The internal use of `RefCell` bleeds into the API
-    pub fn left(&self) -> Option<Rc<RefCell<Node>>> {
-        match self {
-            Node::Internal(node) => {
-                assert!(node.left.is_some());
+    /// The internal nodes are not written; `load_from_reader()` recomputes them.
+    ///
+    /// Fails with `io::ErrorKind::InvalidInput` if the tree has `2^32` leaves
+    /// or more, which don't fit in the format.
+    pub fn save_to_writer(&self, w: &mut impl Write) -> io::Result<()> {
+        let num_leaves = u32::try_from(self.num_leaves()).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} leaves don't fit in a u32", self.num_leaves()),
+            )
+        })?;
+
+        w.write_all(&[match self.hash_algorithm {
+            HashAlgorithm::Blake3 => 0,
+            HashAlgorithm::Sha256 => 1,
+        }])?;
+        w.write_all(&num_leaves.to_le_bytes())?;
+
+        for leaf_hash in self.leaves() {
+            w.write_all(leaf_hash.as_bytes())?;
+        }
+
+        Ok(())
+    }
 
-                node.left.as_ref().cloned()
+    /// Reads a tree written with `save_to_writer()`
+    pub fn load_from_reader(r: &mut impl Read) -> io::Result<Self> {
+        let mut algorithm = [0u8; 1];
+        r.read_exact(&mut algorithm)?;
+        let hash_algorithm = match algorithm {
+            [0] => HashAlgorithm::Blake3,
+            [1] => HashAlgorithm::Sha256,
+            [algorithm] => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid hash algorithm {algorithm}"),
+                ))
             }
+        };
 
-            Node::Leaf(_) => None,
+        let mut num_leaves = [0u8; 4];
+        r.read_exact(&mut num_leaves)?;
+        let num_leaves = u32::from_le_bytes(num_leaves) as usize;
+        if !is_power_of_2(num_leaves) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("number of leaves {num_leaves} is not a power of 2"),
+            ));
         }
+
+        let leaf_hashes = (0..num_leaves)
+            .map(|_| {
+                let mut leaf_hash = [0u8; blake3::OUT_LEN];
+                r.read_exact(&mut leaf_hash)?;
+
+                Ok(Hash::from(leaf_hash))
+            })
+            .collect::<io::Result<Vec<Hash>>>()?;
+
+        Ok(Self::from_leaf_hashes_with_hash(
+            &leaf_hashes,
+            hash_algorithm,
+        ))
     }
 
-    /// Only leaf nodes will return `None`
-    pub fn right(&self) -> Option<Rc<RefCell<Node>>> {
-        match self {
-            Node::Internal(node) => {
-                assert!(node.right.is_some());
+    /// Writes the tree to the file at `path` (see `save_to_writer()`),
+    /// overwriting it if it exists
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.save_to_writer(&mut writer)?;
 
-                node.right.as_ref().cloned()
-            }
-            Node::Leaf(_) => None,
+        writer.flush()
+    }
+
+    /// Reads a tree from the file at `path` (see `load_from_reader()`)
+    pub fn load(path: &Path) -> io::Result<Self> {
+        Self::load_from_reader(&mut BufReader::new(File::open(path)?))
+    }
+
+    /// Returns the hash of the children of the internal node at `index`
+    fn hash_children(&self, index: usize) -> Hash {
+        self.hash_algorithm.hash_all(&[
+            self.nodes[2 * index + 1].as_bytes(),
+            self.nodes[2 * index + 2].as_bytes(),
+        ])
+    }
+
+    /// Returns the Merkle path of every leaf in `indices`. Fails if any of the
+    /// indices is out of bounds.
+    pub fn batch_proof(&self, indices: &[usize]) -> Result<Vec<MerklePath>> {
+        if let Some(index) = indices.iter().find(|index| **index >= self.num_leaves()) {
+            bail!("index {index} out of bounds ({} leaves)", self.num_leaves());
         }
+
+        indices
+            .iter()
+            .map(|index| MerklePath::new(self, *index))
+            .collect()
     }
 
-    /// Returns the sibling, as well as whether that sibling is the left or
-    /// right child of the parent.
-    pub fn sibling(&self) -> Option<(Option<Rc<RefCell<Node>>>, SiblingPosition)> {
-        let parent = self.parent()?;
-
-        let sibling_position = {
-            let left_child_of_parent = parent.borrow().left().unwrap();
-            let left_child_of_parent: &Node = &left_child_of_parent.borrow();
-
-            if left_child_of_parent == self {
-                // If I'm to the left, then my sibling is to the right
-                SiblingPosition::Right
-            } else {
-                SiblingPosition::Left
-            }
-        };
+    pub fn num_leaves(&self) -> usize {
+        self.nodes.len().div_ceil(2)
+    }
 
-        if sibling_position == SiblingPosition::Left {
-            Some((parent.borrow().left(), SiblingPosition::Left))
-        } else {
-            Some((parent.borrow().right(), SiblingPosition::Right))
+    /// Returns the hash function used to build the tree
+    pub fn hash_algorithm(&self) -> HashAlgorithm {
+        self.hash_algorithm
+    }
+
+    /// Returns the hashes of the leaves, from left to right
+    pub fn leaves(&self) -> &[Hash] {
+        &self.nodes[self.num_leaves() - 1..]
+    }
+
+    /// Returns the leaf at `index`, or `None` if it is out of bounds
+    pub fn leaf(&self, index: usize) -> Option<NodeRef<'_>> {
+        (index < self.num_leaves()).then(|| NodeRef {
+            tree: self,
+            index: self.num_leaves() - 1 + index,
+        })
+    }
+
+    /// Returns the number of layers below the root, which is also the length
+    /// of every `MerklePath` in the tree.
+    pub fn height(&self) -> u32 {
+        // The number of leaves is a power of 2
+        self.num_leaves().ilog2()
+    }
+
+    /// Returns the size of a `MerklePath` in the tree, where each hash takes
+    /// 32 bytes, and each `SiblingPosition` 1 byte.
+    pub fn inclusion_proof_size_bytes(&self) -> usize {
+        self.height() as usize * (blake3::OUT_LEN + 1)
+    }
+
+    /// Returns the hash of every node in the tree, in breadth-first order. That
+    /// is, the root comes first, then its children, and so on until the
+    /// leaves, each layer ordered from left to right. There are `2 * n - 1`
+    /// hashes for `n` leaves.
+    ///
+    /// This is exactly the order in which the nodes are stored.
+    pub fn collect_all_hashes(&self) -> Vec<Hash> {
+        self.nodes.clone()
+    }
+
+    /// Returns an iterator over the leaves of the tree, from left to right.
+    pub fn leaves_iter(&self) -> MerkleTreeIter<'_> {
+        MerkleTreeIter {
+            tree: self,
+            leaf_indices: 0..self.num_leaves(),
         }
     }
+}
+
+/// Iterator over the leaves of a `MerkleTree`, from left to right.
+pub struct MerkleTreeIter<'a> {
+    tree: &'a MerkleTree,
+    leaf_indices: Range<usize>,
+}
+
+impl<'a> Iterator for MerkleTreeIter<'a> {
+    type Item = NodeRef<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.leaf_indices
+            .next()
+            .map(|leaf_index| self.tree.leaf(leaf_index).unwrap())
+    }
+}
 
+/// A node of a `MerkleTree`, identified by its index in the tree (see
+/// `MerkleTree`).
+#[derive(Clone, Copy, Debug)]
+pub struct NodeRef<'a> {
+    tree: &'a MerkleTree,
+    index: usize,
+}
+
+impl<'a> NodeRef<'a> {
     pub fn hash(&self) -> Hash {
-        match self {
-            Node::Internal(node) => node.hash,
-            Node::Leaf(node) => node.hash,
+        self.tree.nodes[self.index]
+    }
+
+    /// Only the root node will return `None`
+    pub fn parent(&self) -> Option<NodeRef<'a>> {
+        (self.index > 0).then(|| self.node_at((self.index - 1) / 2))
+    }
+
+    /// Only leaf nodes will return `None`
+    pub fn left(&self) -> Option<NodeRef<'a>> {
+        (!self.is_leaf()).then(|| self.node_at(2 * self.index + 1))
+    }
+
+    /// Only leaf nodes will return `None`
+    pub fn right(&self) -> Option<NodeRef<'a>> {
+        (!self.is_leaf()).then(|| self.node_at(2 * self.index + 2))
+    }
+
+    /// Returns the sibling, as well as whether that sibling is the left or
+    /// right child of the parent. Only the root node will return `None`.
+    pub fn sibling(&self) -> Option<(NodeRef<'a>, SiblingPosition)> {
+        if self.index == 0 {
+            None
+        } else if self.index % 2 == 1 {
+            // Left children have odd indices, so our sibling is to the right
+            Some((self.node_at(self.index + 1), SiblingPosition::Right))
+        } else {
+            Some((self.node_at(self.index - 1), SiblingPosition::Left))
         }
     }
 
-    pub fn set_parent(&mut self, parent: Rc<RefCell<Node>>) {
-        match self {
-            Node::Internal(node) => node.parent = Some(parent),
-            Node::Leaf(node) => node.parent = Some(parent),
+    pub fn is_leaf(&self) -> bool {
+        self.index >= self.tree.num_leaves() - 1
+    }
+
+    fn node_at(&self, index: usize) -> NodeRef<'a> {
+        NodeRef {
+            tree: self.tree,
+            index,
         }
     }
 }
 
-#[derive(Debug, Eq)]
-pub struct LeafNode {
-    parent: Option<Rc<RefCell<Node>>>,
-    hash: Hash,
-}
+/// `blake3::Hash` doesn't implement the serde traits; we serialize it as its
+/// 32 bytes, in the order returned by `Hash::as_bytes()`.
+#[cfg(feature = "serde")]
+pub(crate) mod serde_hash {
+    use blake3::Hash;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
-impl PartialEq for LeafNode {
-    fn eq(&self, other: &Self) -> bool {
-        self.hash == other.hash
+    pub fn serialize<S: Serializer>(hash: &Hash, serializer: S) -> Result<S::Ok, S::Error> {
+        hash.as_bytes().serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Hash, D::Error> {
+        <[u8; blake3::OUT_LEN]>::deserialize(deserializer).map(Hash::from)
     }
 }
 
-#[derive(Debug, Eq)]
-pub struct InternalNode {
-    // Note: We need the `RefCell` only when constructing to set the pointers
-    // right. Once the node is created, we'll never need to mutate.
-    // Is there any better way to use it? Perhaps a few lines of unsafe code?
-    left: Option<Rc<RefCell<Node>>>,
-    right: Option<Rc<RefCell<Node>>>,
-    parent: Option<Rc<RefCell<Node>>>,
-    hash: Hash,
+/// Serializes a `Vec<blake3::Hash>`, where each hash is serialized as in
+/// `serde_hash`.
+#[cfg(feature = "serde")]
+pub(crate) mod serde_hashes {
+    use blake3::Hash;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(hashes: &[Hash], serializer: S) -> Result<S::Ok, S::Error> {
+        hashes
+            .iter()
+            .map(|hash| *hash.as_bytes())
+            .collect::<Vec<_>>()
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Hash>, D::Error> {
+        let hashes = Vec::<[u8; blake3::OUT_LEN]>::deserialize(deserializer)?;
+
+        Ok(hashes.into_iter().map(Hash::from).collect())
+    }
 }
 
-impl PartialEq for InternalNode {
-    fn eq(&self, other: &Self) -> bool {
-        // Note: we only check the hash, because we have reference cycles, which
-        // cause the default implementation of `PartialEq` to stack overflow!
-        self.hash == other.hash
+/// Serializes a `MerklePath::path`, where each hash is serialized as in
+/// `serde_hash`.
+#[cfg(feature = "serde")]
+mod serde_path {
+    use blake3::Hash;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::SiblingPosition;
+
+    pub fn serialize<S: Serializer>(
+        path: &[(Hash, SiblingPosition)],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        path.iter()
+            .map(|(hash, position)| (*hash.as_bytes(), position.clone()))
+            .collect::<Vec<_>>()
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<(Hash, SiblingPosition)>, D::Error> {
+        let path = Vec::<([u8; blake3::OUT_LEN], SiblingPosition)>::deserialize(deserializer)?;
+
+        Ok(path
+            .into_iter()
+            .map(|(hash_bytes, position)| (Hash::from(hash_bytes), position))
+            .collect())
     }
 }
 
@@ -268,19 +619,16 @@ mod tests {
 
         let tree = MerkleTree::new(&leaves);
 
-        for leaf in tree.leaves {
-            let leaf = leaf.borrow();
+        for leaf in tree.leaves_iter() {
             assert!(leaf.right().is_none());
             assert!(leaf.left().is_none());
 
             let parent = leaf.parent().unwrap();
-            let parent = parent.borrow();
 
             assert!(parent.right().is_some());
             assert!(parent.left().is_some());
 
             let root = parent.parent().unwrap();
-            let root = root.borrow();
 
             assert!(root.right().is_some());
             assert!(root.left().is_some());
@@ -297,14 +645,14 @@ mod tests {
 
         let tree = MerkleTree::new(&leaves);
 
-        let left_leaf_in_tree = tree.leaves[1].borrow().sibling().unwrap().0.unwrap();
-        let left_leaf_in_tree = left_leaf_in_tree.borrow();
+        let (left_leaf_in_tree, left_position) = tree.leaf(1).unwrap().sibling().unwrap();
+        let (right_leaf_in_tree, right_position) = tree.leaf(0).unwrap().sibling().unwrap();
 
-        let right_leaf_in_tree = tree.leaves[0].borrow().sibling().unwrap().0.unwrap();
-        let right_leaf_in_tree = right_leaf_in_tree.borrow();
-
-        assert_eq!(left_leaf_in_tree.hash(), hash(&vec![left.as_byte()]));
-        assert_eq!(right_leaf_in_tree.hash(), hash(&vec![right.as_byte()]));
+        assert_eq!(left_leaf_in_tree.hash(), hash(&[left.as_byte()]));
+        assert_eq!(left_position, SiblingPosition::Left);
+        assert_eq!(right_leaf_in_tree.hash(), hash(&[right.as_byte()]));
+        assert_eq!(right_position, SiblingPosition::Right);
+        assert!(tree.leaf(0).unwrap().parent().unwrap().sibling().is_none());
     }
 
     #[test]
@@ -317,4 +665,303 @@ mod tests {
 
         assert!(merkle_path.verify_inclusion(4.into(), tree.root));
     }
+
+    #[test]
+    pub fn test_path_clone_eq() {
+        let leaves: [BaseField; 4] = [1.into(), 2.into(), 3.into(), 4.into()];
+        let tree = MerkleTree::new(&leaves);
+
+        let merkle_path = MerklePath::new(&tree, 3).unwrap();
+        let cloned_path = merkle_path.clone();
+
+        assert!(cloned_path.verify_inclusion(4.into(), tree.root));
+        assert_eq!(merkle_path, cloned_path);
+        assert_ne!(merkle_path, MerklePath::new(&tree, 2).unwrap());
+    }
+
+    #[test]
+    pub fn test_path_recompute_root() {
+        let leaves: [BaseField; 4] = [1.into(), 2.into(), 3.into(), 4.into()];
+        let tree = MerkleTree::new(&leaves);
+
+        for (i, leaf) in leaves.iter().enumerate() {
+            let merkle_path = MerklePath::new(&tree, i).unwrap();
+
+            assert_eq!(merkle_path.len(), 2);
+            assert!(!merkle_path.is_empty());
+            assert_eq!(merkle_path.recompute_root(*leaf), tree.root);
+            assert_ne!(merkle_path.recompute_root(*leaf + 1.into()), tree.root);
+        }
+    }
+
+    #[test]
+    pub fn test_leaves_iter() {
+        let leaves: [BaseField; 4] = [1.into(), 2.into(), 3.into(), 4.into()];
+
+        let tree = MerkleTree::new(&leaves);
+
+        let iter_hashes: Vec<Hash> = tree.leaves_iter().map(|leaf| leaf.hash()).collect();
+        let expected_hashes: Vec<Hash> = leaves.iter().map(|ele| hash(&[ele.as_byte()])).collect();
+
+        assert_eq!(iter_hashes, expected_hashes);
+        assert_eq!(tree.leaves(), &expected_hashes[..]);
+    }
+
+    #[test]
+    pub fn test_sibling_hash_at_depth() {
+        let leaves: [BaseField; 4] = [1.into(), 2.into(), 3.into(), 4.into()];
+
+        let tree = MerkleTree::new(&leaves);
+        let merkle_path = MerklePath::new(&tree, 0).unwrap();
+
+        // The sibling of leaf 0 is leaf 1
+        let (sibling_hash, sibling_position) = merkle_path.sibling_hash_at_depth(0).unwrap();
+        assert_eq!(sibling_hash, hash(&[2u8]));
+        assert_eq!(*sibling_position, SiblingPosition::Right);
+
+        // Right below the root, the sibling is the parent of leaves 2 and 3
+        let (sibling_hash, sibling_position) = merkle_path
+            .sibling_hash_at_depth(merkle_path.path.len() - 1)
+            .unwrap();
+        let expected_hash = {
+            let mut hasher = blake3::Hasher::new();
+            hasher.update(hash(&[3u8]).as_bytes());
+            hasher.update(hash(&[4u8]).as_bytes());
+            hasher.finalize()
+        };
+        assert_eq!(sibling_hash, expected_hash);
+        assert_eq!(*sibling_position, SiblingPosition::Right);
+
+        assert!(merkle_path
+            .sibling_hash_at_depth(merkle_path.path.len())
+            .is_none());
+    }
+
+    /// Appends the hashes of the leaves below `node` to `hashes`, from left to
+    /// right.
+    fn collect_leaf_hashes(node: &NodeRef, hashes: &mut Vec<Hash>) {
+        if node.is_leaf() {
+            hashes.push(node.hash());
+        } else {
+            collect_leaf_hashes(&node.left().unwrap(), hashes);
+            collect_leaf_hashes(&node.right().unwrap(), hashes);
+        }
+    }
+
+    #[test]
+    pub fn test_node_ref_traversal() {
+        let leaves: [BaseField; 8] = [1, 2, 3, 4, 5, 6, 7, 8].map(BaseField::from);
+
+        let tree = MerkleTree::new(&leaves);
+
+        // Climb from the first leaf to the root
+        let leaf = tree.leaf(0).unwrap();
+        let parent = leaf.parent().unwrap();
+        let grandparent = parent.parent().unwrap();
+        let root = grandparent.parent().unwrap();
+        assert!(root.parent().is_none());
+        assert_eq!(root.hash(), tree.root);
+
+        // Walk back down to every leaf
+        let mut leaf_hashes = Vec::new();
+        collect_leaf_hashes(&root, &mut leaf_hashes);
+
+        let expected_hashes: Vec<Hash> = leaves.iter().map(|ele| hash(&[ele.as_byte()])).collect();
+        assert_eq!(leaf_hashes, expected_hashes);
+    }
+
+    #[test]
+    pub fn test_collect_all_hashes() {
+        let leaves: [BaseField; 4] = [1.into(), 2.into(), 3.into(), 4.into()];
+
+        let tree = MerkleTree::new(&leaves);
+        let hashes = tree.collect_all_hashes();
+
+        assert_eq!(hashes.len(), 2 * leaves.len() - 1);
+        assert_eq!(hashes[0], tree.root);
+
+        let leaf_hashes: Vec<Hash> = leaves.iter().map(|ele| hash(&[ele.as_byte()])).collect();
+        assert_eq!(hashes[3..], leaf_hashes[..]);
+    }
+
+    #[test]
+    pub fn test_root_only() {
+        let leaves: Vec<BaseField> = (1..=8).map(BaseField::from).collect();
+
+        for leaves in [&leaves[0..4], &leaves[..]] {
+            assert_eq!(root_only(leaves), MerkleTree::new(leaves).root);
+            assert_eq!(
+                root_only_with_hash(leaves, HashAlgorithm::Sha256),
+                MerkleTree::new_with_hash(leaves, HashAlgorithm::Sha256).root
+            );
+        }
+    }
+
+    #[test]
+    pub fn test_tree_metadata() {
+        let leaves: [BaseField; 4] = [1.into(), 2.into(), 3.into(), 4.into()];
+        let tree = MerkleTree::new(&leaves);
+
+        assert_eq!(tree.num_leaves(), 4);
+        assert_eq!(tree.height(), 2);
+        assert_eq!(tree.inclusion_proof_size_bytes(), 2 * 33);
+        assert_eq!(
+            MerklePath::new(&tree, 0).unwrap().len(),
+            tree.height() as usize
+        );
+
+        let single_leaf_tree = MerkleTree::new(&[BaseField::one()]);
+        assert_eq!(single_leaf_tree.height(), 0);
+        assert_eq!(single_leaf_tree.inclusion_proof_size_bytes(), 0);
+    }
+
+    #[test]
+    pub fn test_batch_proof() {
+        let leaves: Vec<BaseField> = (1..=8).map(BaseField::from).collect();
+        let tree = MerkleTree::new(&leaves);
+
+        let indices = [0, 3, 5, 3];
+        let batch = MerkleBatchPath {
+            paths: tree.batch_proof(&indices).unwrap(),
+        };
+        assert_eq!(batch.paths[1], MerklePath::new(&tree, 3).unwrap());
+
+        let elements: Vec<BaseField> = indices.iter().map(|index| leaves[*index]).collect();
+        assert!(batch.verify_batch(&elements, tree.root));
+
+        // Wrong element, or wrong number of elements
+        let mut wrong_elements = elements.clone();
+        wrong_elements[2] += BaseField::one();
+        assert!(!batch.verify_batch(&wrong_elements, tree.root));
+        assert!(!batch.verify_batch(&elements[1..], tree.root));
+
+        assert!(tree.batch_proof(&[1, 8]).is_err());
+
+        // A tree built with another hash algorithm
+        let sha256_tree = MerkleTree::new_with_hash(&leaves, HashAlgorithm::Sha256);
+        let sha256_batch = MerkleBatchPath {
+            paths: sha256_tree.batch_proof(&indices).unwrap(),
+        };
+        assert!(sha256_batch.verify_batch_with_hash(
+            &elements,
+            sha256_tree.root,
+            HashAlgorithm::Sha256
+        ));
+        assert!(!sha256_batch.verify_batch(&elements, sha256_tree.root));
+    }
+
+    #[test]
+    pub fn test_from_leaf_hashes() {
+        let leaves: [BaseField; 4] = [1.into(), 2.into(), 3.into(), 4.into()];
+        let leaf_hashes = leaves.map(|x| hash(&[x.as_byte()]));
+
+        let tree = MerkleTree::from_leaf_hashes(&leaf_hashes);
+        assert_eq!(tree.root, MerkleTree::new(&leaves).root);
+        assert!(MerklePath::new(&tree, 2)
+            .unwrap()
+            .verify_inclusion(leaves[2], tree.root));
+    }
+
+    #[test]
+    pub fn test_tree_is_send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+
+        assert_send_sync::<MerkleTree>();
+    }
+
+    #[test]
+    pub fn test_leaf_out_of_bounds() {
+        let leaves: [BaseField; 4] = [1.into(), 2.into(), 3.into(), 4.into()];
+        let tree = MerkleTree::new(&leaves);
+
+        assert!(tree.leaf(4).is_none());
+        assert!(MerklePath::new(&tree, 4).is_err());
+    }
+
+    #[test]
+    pub fn test_update_leaf() {
+        let leaves: [BaseField; 4] = [1.into(), 2.into(), 3.into(), 4.into()];
+        let mut tree = MerkleTree::new(&leaves);
+
+        tree.update_leaf(2, 7.into()).unwrap();
+
+        let merkle_path = MerklePath::new(&tree, 2).unwrap();
+        assert!(merkle_path.verify_inclusion(7.into(), tree.root));
+        assert!(!merkle_path.verify_inclusion(3.into(), tree.root));
+        assert_eq!(
+            tree.root,
+            MerkleTree::new(&[1, 2, 7, 4].map(BaseField::from)).root
+        );
+
+        assert!(tree.update_leaf(4, 7.into()).is_err());
+
+        let mut sha_tree = MerkleTree::new_with_hash(&leaves, HashAlgorithm::Sha256);
+        sha_tree.update_leaf(0, 7.into()).unwrap();
+        assert_eq!(
+            sha_tree.root,
+            MerkleTree::new_with_hash(&[7, 2, 3, 4].map(BaseField::from), HashAlgorithm::Sha256)
+                .root
+        );
+    }
+
+    #[test]
+    pub fn test_verify_all() {
+        let leaves: [BaseField; 8] = [1, 2, 3, 4, 5, 6, 7, 8].map(BaseField::from);
+        let mut tree = MerkleTree::new_with_hash(&leaves, HashAlgorithm::Sha256);
+
+        assert!(tree.verify_all());
+
+        tree.update_leaf(5, 0.into()).unwrap();
+        assert!(tree.verify_all());
+
+        // Corrupt an internal node (the parent of leaves 2 and 3)
+        tree.nodes[4] = hash(b"corrupted");
+        assert!(!tree.verify_all());
+
+        assert!(MerkleTree::new(&[BaseField::one()]).verify_all());
+    }
+
+    #[test]
+    pub fn test_save_load() {
+        let leaves: [BaseField; 8] = [1, 2, 3, 4, 5, 6, 7, 8].map(BaseField::from);
+
+        for hash_algorithm in [HashAlgorithm::Blake3, HashAlgorithm::Sha256] {
+            let tree = MerkleTree::new_with_hash(&leaves, hash_algorithm);
+
+            let mut bytes = Vec::new();
+            tree.save_to_writer(&mut bytes).unwrap();
+            assert_eq!(bytes.len(), 1 + 4 + 8 * 32);
+
+            let loaded_tree = MerkleTree::load_from_reader(&mut bytes.as_slice()).unwrap();
+            assert_eq!(loaded_tree.root, tree.root);
+            assert_eq!(loaded_tree.collect_all_hashes(), tree.collect_all_hashes());
+
+            // Truncated input
+            assert!(MerkleTree::load_from_reader(&mut &bytes[..bytes.len() - 1]).is_err());
+        }
+
+        // Not a power of 2
+        let mut bytes = vec![0];
+        bytes.extend_from_slice(&3u32.to_le_bytes());
+        bytes.extend_from_slice(&[0u8; 3 * 32]);
+        assert!(MerkleTree::load_from_reader(&mut bytes.as_slice()).is_err());
+
+        // Round trip through a file
+        let tree = MerkleTree::new(&leaves);
+        let path = std::env::temp_dir().join(format!("stark-102-merkle-{}", std::process::id()));
+        tree.save(&path).unwrap();
+        let loaded_tree = MerkleTree::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded_tree.unwrap().root, tree.root);
+    }
+
+    #[test]
+    pub fn test_leaf_index() {
+        let leaves: [BaseField; 8] = [1, 2, 3, 4, 5, 6, 7, 8].map(BaseField::from);
+        let tree = MerkleTree::new(&leaves);
+
+        for index in 0..leaves.len() {
+            assert_eq!(MerklePath::new(&tree, index).unwrap().leaf_index(), index);
+        }
+    }
 }
@@ -0,0 +1,162 @@
+use crate::{field::BaseField, util::is_power_of_2};
+
+/// Computes the Number Theoretic Transform (i.e. the Fast Fourier Transform
+/// over a finite field) of `values`, in place. That is, given the coefficients
+/// of a polynomial `p`, `values` is overwritten with the evaluations
+/// `[p(g^0), p(g^1), ..., p(g^(n-1))]`, where `g` is `generator`.
+///
+/// We use the iterative radix-2 Cooley-Tukey algorithm, which runs in O(n log
+/// n) instead of the O(n^2) it takes to evaluate the polynomial at every point.
+///
+/// Preconditions:
+/// - `values.len()` is a power of 2
+/// - `generator` generates a multiplicative subgroup of size `values.len()`
+pub fn ntt_forward(values: &mut [BaseField], generator: BaseField) {
+    let n = values.len();
+    assert!(is_power_of_2(n), "NTT size must be a power of 2, got {n}");
+    assert_eq!(
+        generator.exp(n as u8),
+        BaseField::one(),
+        "{generator} doesn't generate a subgroup of size {n}"
+    );
+
+    bit_reverse_permutation(values);
+
+    // Merge the NTTs of size `len / 2` into NTTs of size `len`
+    let mut len = 2;
+    while len <= n {
+        // Generator of the subgroup of size `len`
+        let root = generator.exp((n / len) as u8);
+
+        for chunk in values.chunks_mut(len) {
+            let mut twiddle = BaseField::one();
+
+            for i in 0..len / 2 {
+                let even = chunk[i];
+                let odd = chunk[i + len / 2] * twiddle;
+
+                chunk[i] = even + odd;
+                chunk[i + len / 2] = even - odd;
+
+                twiddle *= root;
+            }
+        }
+
+        len *= 2;
+    }
+}
+
+/// Inverse of `ntt_forward()`; that is, given the evaluations of a polynomial
+/// over the subgroup generated by `generator`, `values` is overwritten with its
+/// coefficients.
+///
+/// The inverse NTT is the same as the forward NTT using the inverse of the
+/// generator, followed by a division by `n`.
+///
+/// Preconditions: same as `ntt_forward()`.
+pub fn ntt_inverse(values: &mut [BaseField], generator: BaseField) {
+    ntt_forward(values, generator.mult_inv());
+
+    let n_inv = BaseField::from(values.len() as u8).mult_inv();
+    for value in values.iter_mut() {
+        *value *= n_inv;
+    }
+}
+
+/// Reorders `values` such that the element at index `i` moves to the index
+/// whose binary representation is `i`'s reversed.
+fn bit_reverse_permutation(values: &mut [BaseField]) {
+    let n = values.len();
+    let num_bits = n.trailing_zeros();
+
+    for i in 0..n {
+        let j = i.reverse_bits() >> (usize::BITS - num_bits);
+
+        if i < j {
+            values.swap(i, j);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::poly::Polynomial;
+
+    /// Generator of the multiplicative subgroup of size 4
+    const GENERATOR_4: BaseField = BaseField::new(13);
+    /// Generator of the multiplicative subgroup of size 8
+    const GENERATOR_8: BaseField = BaseField::new(9);
+
+    #[test]
+    pub fn ntt_forward_is_evaluation() {
+        let poly = Polynomial::new(vec![6.into(), 16.into(), 2.into(), 13.into()]);
+
+        let mut values = poly.coefficients().to_vec();
+        ntt_forward(&mut values, GENERATOR_4);
+
+        let expected: Vec<BaseField> = (0..4).map(|i| poly.eval(GENERATOR_4.exp(i))).collect();
+
+        assert_eq!(values, expected);
+    }
+
+    #[test]
+    pub fn ntt_round_trip() {
+        let coeffs_4: Vec<BaseField> = vec![1.into(), 2.into(), 3.into(), 4.into()];
+        let mut values = coeffs_4.clone();
+        ntt_forward(&mut values, GENERATOR_4);
+        ntt_inverse(&mut values, GENERATOR_4);
+        assert_eq!(values, coeffs_4);
+
+        let coeffs_8: Vec<BaseField> = (0..8).map(|i| BaseField::from(3 * i + 5)).collect();
+        let mut values = coeffs_8.clone();
+        ntt_forward(&mut values, GENERATOR_8);
+        ntt_inverse(&mut values, GENERATOR_8);
+        assert_eq!(values, coeffs_8);
+    }
+
+    #[test]
+    pub fn ntt_mul_matches_poly_mul() {
+        fn ntt_mul(lhs: &Polynomial, rhs: &Polynomial) -> Vec<BaseField> {
+            let mut lhs_values = lhs.coefficients().to_vec();
+            lhs_values.resize(8, BaseField::zero());
+            let mut rhs_values = rhs.coefficients().to_vec();
+            rhs_values.resize(8, BaseField::zero());
+
+            ntt_forward(&mut lhs_values, GENERATOR_8);
+            ntt_forward(&mut rhs_values, GENERATOR_8);
+
+            let mut product: Vec<BaseField> = lhs_values
+                .into_iter()
+                .zip(rhs_values)
+                .map(|(lhs_y, rhs_y)| lhs_y * rhs_y)
+                .collect();
+            ntt_inverse(&mut product, GENERATOR_8);
+
+            product
+        }
+
+        // Polynomials of degree <= 3, such that the product has degree <= 6
+        // and fits in a domain of size 8
+        let polys: Vec<Polynomial> = (0..17u8)
+            .map(|i| {
+                Polynomial::new(vec![
+                    i.into(),
+                    (i * 3).into(),
+                    (i * 5 + 1).into(),
+                    (i * 7 + 2).into(),
+                ])
+            })
+            .collect();
+
+        for lhs in polys.iter() {
+            for rhs in polys.iter() {
+                let mut expected = (lhs.clone() * rhs.clone()).coefficients().to_vec();
+                expected.resize(8, BaseField::zero());
+
+                assert_eq!(ntt_mul(lhs, rhs), expected);
+            }
+        }
+    }
+}
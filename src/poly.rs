@@ -1,12 +1,15 @@
 use std::{
     cmp::min,
     iter::Sum,
-    ops::{Add, AddAssign, Div, Mul, MulAssign},
+    ops::{Add, AddAssign, Div, Mul, MulAssign, Sub, SubAssign},
 };
 
 use anyhow::bail;
 
-use crate::field::BaseField;
+use crate::{
+    field::{batch_mult_inv, BaseField},
+    ntt::ntt_forward,
+};
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Polynomial {
@@ -33,10 +36,89 @@ impl Polynomial {
         }
     }
 
+    pub fn coefficients(&self) -> &[BaseField] {
+        &self.coefficients
+    }
+
     pub fn degree(&self) -> usize {
         self.coefficients.len() - 1
     }
 
+    /// Removes trailing zero coefficients, such that `degree()` returns the
+    /// actual degree of the polynomial. The zero polynomial keeps a single
+    /// coefficient.
+    pub fn trim(&mut self) {
+        while self.coefficients.len() > 1 && self.coefficients.last() == Some(&BaseField::zero()) {
+            self.coefficients.pop();
+        }
+    }
+
+    /// Consuming version of `trim()`
+    pub fn trimmed(mut self) -> Self {
+        self.trim();
+        self
+    }
+
+    /// Returns `true` if all coefficients are 0
+    pub fn is_zero(&self) -> bool {
+        self.coefficients
+            .iter()
+            .all(|coeff| *coeff == BaseField::zero())
+    }
+
+    /// Returns the polynomial `(x - domain[0]) * ... * (x - domain[n-1])`,
+    /// which evaluates to 0 everywhere on `domain`.
+    pub fn vanishing_polynomial(domain: &[BaseField]) -> Self {
+        domain.iter().fold(Polynomial::one(), |acc, domain_ele| {
+            acc * Polynomial::new(vec![-*domain_ele, 1.into()])
+        })
+    }
+
+    /// Divides `self` by `divisor`, and errors if `divisor` doesn't divide
+    /// `self` exactly (i.e. if the remainder is not zero).
+    pub fn div_exact(&self, divisor: &Polynomial) -> anyhow::Result<Polynomial> {
+        let (quotient, remainder) = poly_div(self, divisor)?;
+
+        if !remainder.is_zero() {
+            bail!(
+                "division is not exact; remainder: {:?}",
+                remainder.coefficients
+            );
+        }
+
+        Ok(quotient)
+    }
+
+    /// Divides `self` by `x - z`, using synthetic division. Returns the
+    /// quotient and the remainder; the remainder is `self(z)`.
+    pub fn div_by_linear(&self, z: BaseField) -> (Polynomial, BaseField) {
+        let mut quotient = vec![BaseField::zero(); self.coefficients.len().saturating_sub(1)];
+
+        // Horner's method: the intermediate values are the quotient's coefficients
+        let mut acc = BaseField::zero();
+        for (i, coeff) in self.coefficients.iter().enumerate().rev() {
+            acc = acc * z + *coeff;
+            if i > 0 {
+                quotient[i - 1] = acc;
+            }
+        }
+
+        if quotient.is_empty() {
+            (Polynomial::zero(), acc)
+        } else {
+            (Polynomial::new(quotient).trimmed(), acc)
+        }
+    }
+
+    /// Returns every root of the polynomial in `BaseField`, in increasing
+    /// order. Our field is small enough that we simply try every element.
+    pub fn roots(&self) -> Vec<BaseField> {
+        (0..17u8)
+            .map(BaseField::from)
+            .filter(|x| self.eval(*x) == BaseField::zero())
+            .collect()
+    }
+
     /// Evaluates the polynomial at `x`
     pub fn eval(&self, x: BaseField) -> BaseField {
         let mut result = BaseField::zero();
@@ -56,6 +138,26 @@ impl Polynomial {
             .collect()
     }
 
+    /// Same as `eval_domain()`, but evaluates the polynomial at every point of
+    /// the domain in parallel. For our tiny domains, this is slower than the
+    /// sequential version; it only pays off for domains with many thousands
+    /// of elements.
+    #[cfg(feature = "parallel")]
+    pub fn eval_domain_par(&self, domain: &[BaseField]) -> Vec<BaseField> {
+        use rayon::prelude::*;
+
+        domain
+            .par_iter()
+            .map(|domain_ele| self.eval(*domain_ele))
+            .collect()
+    }
+
+    /// Evaluates every polynomial in `polys` at every point in `points`, such
+    /// that `result[i][j] = polys[i].eval(points[j])`.
+    pub fn eval_table(polys: &[Polynomial], points: &[BaseField]) -> Vec<Vec<BaseField>> {
+        polys.iter().map(|poly| poly.eval_domain(points)).collect()
+    }
+
     // https://mathworld.wolfram.com/LagrangeInterpolatingPolynomial.html
     pub fn lagrange_interp(
         domain: &[BaseField],
@@ -65,34 +167,125 @@ impl Polynomial {
             bail!("domain and evaluations have different sizes");
         }
 
+        let denominators_inv = Self::lagrange_denominators_inv(domain);
+
         let interpolated_poly = (0..domain.len())
-            .map(|j| Self::partial_lagrange_poly(j, domain, evaluations))
+            .map(|j| Self::lagrange_numerator(j, domain) * (evaluations[j] * denominators_inv[j]))
             .sum();
 
+        debug_assert!(
+            check_interpolation(&interpolated_poly, domain, evaluations).is_ok(),
+            "interpolated polynomial doesn't pass through the given points"
+        );
+
         Ok(interpolated_poly)
     }
 
-    fn partial_lagrange_poly(j: usize, domain: &[BaseField], evaluations: &[BaseField]) -> Self {
-        let x_j = domain[j];
-        let y_j = evaluations[j];
+    /// Returns the Lagrange basis polynomials `L_0, ..., L_{n-1}` for `domain`,
+    /// where `L_i(domain[j])` is 1 if `i == j`, and 0 otherwise.
+    pub fn lagrange_basis(domain: &[BaseField]) -> anyhow::Result<Vec<Self>> {
+        for (i, x_i) in domain.iter().enumerate() {
+            if domain[i + 1..].contains(x_i) {
+                bail!("domain contains duplicate element {x_i}");
+            }
+        }
 
-        let (numerator, denominator) = {
-            let mut numerator = Polynomial::one();
-            let mut denominator = BaseField::one();
+        let denominators_inv = Self::lagrange_denominators_inv(domain);
 
-            for domain_ele in domain.iter() {
-                if x_j != *domain_ele {
-                    // x - x_k
-                    numerator *= Polynomial::new(vec![domain_ele.minus(), 1.into()]);
+        let basis = (0..domain.len())
+            .map(|j| Self::lagrange_numerator(j, domain) * denominators_inv[j])
+            .collect();
 
-                    denominator *= x_j - *domain_ele;
-                }
+        Ok(basis)
+    }
+
+    /// Returns `(x - x_0) ... (x - x_n)`, skipping `x - x_j`
+    fn lagrange_numerator(j: usize, domain: &[BaseField]) -> Self {
+        let x_j = domain[j];
+
+        let mut numerator = Polynomial::one();
+        for domain_ele in domain.iter() {
+            if x_j != *domain_ele {
+                // x - x_k
+                numerator *= Polynomial::new(vec![-*domain_ele, 1.into()]);
             }
+        }
 
-            (numerator, denominator)
-        };
+        numerator
+    }
+
+    /// Returns the inverses of `(x_j - x_0) ... (x_j - x_n)` (skipping `x_j -
+    /// x_j`) for every `j`. All the inverses are computed at once with
+    /// `batch_mult_inv()`.
+    fn lagrange_denominators_inv(domain: &[BaseField]) -> Vec<BaseField> {
+        let denominators: Vec<BaseField> = domain
+            .iter()
+            .map(|x_j| {
+                domain
+                    .iter()
+                    .filter(|domain_ele| *domain_ele != x_j)
+                    .fold(BaseField::one(), |acc, domain_ele| {
+                        acc * (*x_j - *domain_ele)
+                    })
+            })
+            .collect();
+
+        batch_mult_inv(&denominators)
+    }
+
+    /// Returns the polynomial `q(x) = p(-x)`. That is, the odd-degree
+    /// coefficients are negated, and the even-degree ones are unchanged.
+    pub fn neg_x(&self) -> Polynomial {
+        let coefficients = self
+            .coefficients
+            .iter()
+            .enumerate()
+            .map(|(i, coeff)| if i % 2 == 1 { -coeff } else { *coeff })
+            .collect();
+
+        Polynomial::new(coefficients)
+    }
+
+    /// Returns the polynomial `q(x) = p(c * x)`. That is, the coefficient of
+    /// degree `i` is multiplied by `c^i`.
+    pub fn scale_x(&self, c: BaseField) -> Polynomial {
+        let coefficients = self
+            .coefficients
+            .iter()
+            .enumerate()
+            .map(|(i, coeff)| *coeff * c.exp(i as u8))
+            .collect();
 
-        (numerator * y_j) / denominator
+        Polynomial::new(coefficients)
+    }
+
+    /// Splits the polynomial into `k` parts `p_0, ..., p_{k-1}` such that
+    ///
+    ///   p(x) = p_0(x^k) + x * p_1(x^k) + ... + x^(k-1) * p_{k-1}(x^k)
+    ///
+    /// That is, part `j` holds the coefficients of degree `i` where `i % k =
+    /// j`. For `k = 2`, these are the even and odd parts used in `fri_step()`.
+    /// A part without any coefficient is the zero polynomial.
+    pub fn split_into_k_parts(&self, k: usize) -> Vec<Polynomial> {
+        assert!(k > 0, "cannot split a polynomial into 0 parts");
+
+        (0..k)
+            .map(|j| {
+                let coeffs: Vec<_> = self
+                    .coefficients
+                    .iter()
+                    .skip(j)
+                    .step_by(k)
+                    .copied()
+                    .collect();
+
+                if coeffs.is_empty() {
+                    Polynomial::zero()
+                } else {
+                    Polynomial::new(coeffs)
+                }
+            })
+            .collect()
     }
 
     /// Performs one FRI step on the polynomial.
@@ -107,13 +300,13 @@ impl Polynomial {
     /// And the output polynomial is:
     ///   output_poly(x) = (4 + 5*beta)x + (7 + 3*beta)
     ///
-    /// Precondition: The polynomial is not a constant (i.e. only one coefficient).
+    /// A constant polynomial has no odd part, and is therefore its own FRI
+    /// step. This happens when the constraint polynomials have a lower degree
+    /// than expected (e.g. for the trace `1, 1, 1, 1`).
     pub fn fri_step(self, beta: BaseField) -> Self {
-        assert!(
-            self.coefficients.len() > 1,
-            "num coefficients: {}",
-            self.coefficients.len()
-        );
+        if self.coefficients.len() <= 1 {
+            return self;
+        }
 
         println!(
             "FRI step on coefficients {:?} with beta={beta}",
@@ -130,6 +323,292 @@ impl Polynomial {
     }
 }
 
+/// Evaluates `lhs` and `rhs` at every point of `domain`, and errors on the
+/// first point where they disagree. This is useful to check that two
+/// polynomials agree over a domain (e.g. that a constraint polynomial vanishes
+/// over the trace domain) without comparing their coefficients.
+pub fn check_polynomial_identity_over_domain(
+    lhs: &Polynomial,
+    rhs: &Polynomial,
+    domain: &[BaseField],
+) -> anyhow::Result<()> {
+    for x in domain {
+        let lhs_x = lhs.eval(*x);
+        let rhs_x = rhs.eval(*x);
+
+        if lhs_x != rhs_x {
+            bail!("polynomials differ at x={x}: lhs(x)={lhs_x}, rhs(x)={rhs_x}");
+        }
+    }
+
+    Ok(())
+}
+
+/// Computes a hash of the polynomial's coefficients, starting from the constant
+/// term. Two polynomials with the same coefficients hash to the same value.
+///
+/// Note: `Polynomial` doesn't strip trailing zero coefficients, so e.g. `1` and
+/// `1 + 0x` hash to different values.
+pub fn polynomial_hash(poly: &Polynomial) -> blake3::Hash {
+    let mut hasher = blake3::Hasher::new();
+
+    for coeff in poly.coefficients.iter() {
+        hasher.update(&[coeff.as_byte()]);
+    }
+
+    hasher.finalize()
+}
+
+/// Checks that `poly` passes through every point `(domain[i], evaluations[i])`.
+pub fn check_interpolation(
+    poly: &Polynomial,
+    domain: &[BaseField],
+    evaluations: &[BaseField],
+) -> anyhow::Result<()> {
+    if domain.len() != evaluations.len() {
+        bail!("domain and evaluations have different sizes");
+    }
+
+    for (x, expected_y) in domain.iter().zip(evaluations.iter()) {
+        let y = poly.eval(*x);
+
+        if y != *expected_y {
+            bail!("poly({x}) = {y}, but expected {expected_y}");
+        }
+    }
+
+    Ok(())
+}
+
+/// Polynomial long division. Returns `(quotient, remainder)` such that
+/// `dividend = quotient * divisor + remainder`, where the remainder has degree
+/// less than the divisor.
+pub fn poly_div(
+    dividend: &Polynomial,
+    divisor: &Polynomial,
+) -> anyhow::Result<(Polynomial, Polynomial)> {
+    // Trailing zero coefficients don't count towards the degree
+    let divisor = divisor.clone().trimmed();
+    if divisor.is_zero() {
+        bail!("cannot divide by the zero polynomial");
+    }
+    let divisor_degree = divisor.degree();
+    let divisor_leading_coeff = divisor.coefficients[divisor_degree];
+
+    let mut remainder = dividend.coefficients.clone();
+    if remainder.len() <= divisor_degree {
+        return Ok((Polynomial::zero(), dividend.clone()));
+    }
+
+    let mut quotient = vec![BaseField::zero(); remainder.len() - divisor_degree];
+
+    // Cancel the leading term of the remainder, from the highest degree down
+    for i in (0..quotient.len()).rev() {
+        let coeff = remainder[i + divisor_degree] / divisor_leading_coeff;
+        quotient[i] = coeff;
+
+        for (j, divisor_coeff) in divisor.coefficients.iter().enumerate() {
+            remainder[i + j] = remainder[i + j] - coeff * *divisor_coeff;
+        }
+    }
+
+    remainder.truncate(divisor_degree.max(1));
+
+    Ok((
+        Polynomial::new(quotient).trimmed(),
+        Polynomial::new(remainder).trimmed(),
+    ))
+}
+
+/// Computes the product of all `polys`. Rather than multiplying them one after
+/// the other, we multiply them pairwise in a binary tree, such that the
+/// polynomials being multiplied at each level have similar degrees.
+///
+/// Returns `Polynomial::one()` if `polys` is empty.
+pub fn product_polynomial(polys: &[Polynomial]) -> Polynomial {
+    match polys.len() {
+        0 => Polynomial::one(),
+        1 => polys[0].clone(),
+        len => {
+            let (left, right) = polys.split_at(len / 2);
+
+            product_polynomial(left) * product_polynomial(right)
+        }
+    }
+}
+
+/// Computes `sum_i poly(domain[i])`, without building the vector of
+/// evaluations.
+///
+/// For `p(x) = sum_j a_j x^j`, this equals `sum_j a_j * (sum_i domain[i]^j)`.
+/// When `domain` is a cyclic group (or a coset of one) of size `n`, `sum_i
+/// domain[i]^j = 0` for `1 <= j < n`. Hence, if `deg(p) < n`, the sum is
+/// simply `n * a_0`.
+pub fn sum_over_domain(poly: &Polynomial, domain: &[BaseField]) -> BaseField {
+    domain
+        .iter()
+        .fold(BaseField::zero(), |acc, x| acc + poly.eval(*x))
+}
+
+/// Evaluates the polynomial interpolated over `domain` and `evaluations` at
+/// `point`, without ever constructing the polynomial. We use the (second form
+/// of the) barycentric formula:
+///
+///   p(x) = (sum_j w_j * y_j / (x - x_j)) / (sum_j w_j / (x - x_j))
+///
+/// where `w_j = 1 / prod_{k != j} (x_j - x_k)` are the barycentric weights.
+/// Note that the formula is undefined when `x` is in the domain, in which case
+/// we return the corresponding evaluation directly.
+pub fn lagrange_value_at(
+    domain: &[BaseField],
+    evaluations: &[BaseField],
+    point: BaseField,
+) -> anyhow::Result<BaseField> {
+    if domain.len() != evaluations.len() {
+        bail!("domain and evaluations have different sizes");
+    }
+
+    if let Some(idx) = domain.iter().position(|x_j| *x_j == point) {
+        return Ok(evaluations[idx]);
+    }
+
+    let mut numerator = BaseField::zero();
+    let mut denominator = BaseField::zero();
+
+    for (j, (x_j, y_j)) in domain.iter().zip(evaluations.iter()).enumerate() {
+        let w_j = {
+            let mut w_j_inv = BaseField::one();
+            for (k, x_k) in domain.iter().enumerate() {
+                if k != j {
+                    w_j_inv *= *x_j - *x_k;
+                }
+            }
+
+            w_j_inv.mult_inv()
+        };
+
+        let term = w_j / (point - *x_j);
+        numerator += term * *y_j;
+        denominator += term;
+    }
+
+    Ok(numerator / denominator)
+}
+
+/// Returns `true` if `p` evaluates to 0 everywhere on `domain`. Rather than
+/// evaluating `p` at every point, we use the fact that `p` vanishes on `domain`
+/// if and only if the vanishing polynomial of `domain` divides `p`.
+pub fn vanishes_on_domain_fast(p: &Polynomial, domain: &[BaseField]) -> bool {
+    let vanishing_poly = Polynomial::vanishing_polynomial(domain);
+
+    let (_, remainder) =
+        poly_div(p, &vanishing_poly).expect("vanishing polynomial is monic, and hence not zero");
+
+    remainder.is_zero()
+}
+
+/// Computes the Discrete Fourier Transform of `coefficients`; that is, the
+/// evaluations `[p(w^0), p(w^1), ..., p(w^(n-1))]` of the polynomial `p` with
+/// the given coefficients, where `w` is `primitive_root`, and `n` is the number
+/// of coefficients.
+///
+/// This is the textbook O(n^2) definition, where each output is computed
+/// independently. `ntt::ntt_forward()` computes the same thing in O(n log n),
+/// but requires `n` to be a power of 2.
+///
+/// Precondition: `primitive_root` is a primitive `n`-th root of unity
+pub fn dft(coefficients: &[BaseField], primitive_root: BaseField) -> Vec<BaseField> {
+    let poly = Polynomial::new(coefficients.to_vec());
+
+    (0..coefficients.len())
+        .map(|k| poly.eval(primitive_root.exp(k as u8)))
+        .collect()
+}
+
+/// Inverse of `dft()`; that is, recovers the coefficients of a polynomial from
+/// its evaluations over the powers of `primitive_root`. This is the DFT with
+/// the inverse root, divided by `n`.
+///
+/// Precondition: same as `dft()`.
+pub fn idft(evaluations: &[BaseField], primitive_root: BaseField) -> Vec<BaseField> {
+    let n_inv = BaseField::from(evaluations.len() as u8).mult_inv();
+
+    dft(evaluations, primitive_root.mult_inv())
+        .into_iter()
+        .map(|coeff| coeff * n_inv)
+        .collect()
+}
+
+/// Evaluates `poly` over the coset `{shift * generator^0, shift *
+/// generator^1, ..., shift * generator^(size-1)}`. This gives the same result
+/// as `poly.eval_domain()` over the coset, but in O(n log n) using the NTT.
+///
+/// Since `p(shift * x) = q(x)` with `q = p.scale_x(shift)`, evaluating `p`
+/// over the coset is the same as evaluating `q` over the subgroup generated by
+/// `generator`, which is what the NTT does. Coefficients of degree `size` and
+/// above are folded back, since `x^size = 1` on the subgroup.
+///
+/// Preconditions: same as `ntt_forward()`.
+pub fn coset_evaluation(
+    poly: &Polynomial,
+    shift: BaseField,
+    generator: BaseField,
+    size: usize,
+) -> Vec<BaseField> {
+    let mut values = vec![BaseField::zero(); size];
+    for (i, coeff) in poly.scale_x(shift).coefficients().iter().enumerate() {
+        values[i % size] += *coeff;
+    }
+
+    ntt_forward(&mut values, generator);
+
+    values
+}
+
+/// Evaluates `poly` at `generator^k`, using `p(g^k) = sum_j coeff_j * g^(jk)`.
+/// `g^k` is computed once via `exp()`, and each term's power of it is obtained
+/// from the previous one with a single multiplication.
+///
+/// Since every nonzero element's order divides 16, `k` is reduced modulo 16
+/// beforehand.
+pub fn eval_at_generator_power(poly: &Polynomial, generator: BaseField, k: usize) -> BaseField {
+    let g_k = generator.exp((k % 16) as u8);
+
+    let mut result = BaseField::zero();
+    let mut g_jk = BaseField::one();
+    for coeff in poly.coefficients() {
+        result += *coeff * g_jk;
+        g_jk *= g_k;
+    }
+
+    result
+}
+
+/// Returns an iterator over all `17^(d+1)` polynomials of degree at most `d`,
+/// for exhaustive testing. The polynomials are trimmed, so each one is yielded
+/// exactly once (with its actual degree).
+///
+/// Panics if `d > 3`, as there are too many polynomials to go through.
+pub fn all_polynomials_of_degree_at_most(d: usize) -> impl Iterator<Item = Polynomial> {
+    assert!(d <= 3, "too many polynomials of degree at most {d}");
+
+    let num_coeffs = d + 1;
+    let num_polys = 17usize.pow(num_coeffs as u32);
+
+    (0..num_polys).map(move |mut idx| {
+        // The coefficients are the base-17 digits of `idx`
+        let coefficients = (0..num_coeffs)
+            .map(|_| {
+                let coeff = BaseField::from((idx % 17) as u8);
+                idx /= 17;
+                coeff
+            })
+            .collect();
+
+        Polynomial::new(coefficients).trimmed()
+    })
+}
+
 impl Add for Polynomial {
     type Output = Self;
 
@@ -162,6 +641,42 @@ impl AddAssign for Polynomial {
     }
 }
 
+impl Sub for Polynomial {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        let mut coefficients_diff = Vec::new();
+
+        let min_coeffs_len = min(self.coefficients.len(), rhs.coefficients.len());
+
+        for i in 0..min_coeffs_len {
+            coefficients_diff.push(self.coefficients[i] - rhs.coefficients[i]);
+        }
+
+        if self.coefficients.len() > min_coeffs_len {
+            coefficients_diff.extend_from_slice(&self.coefficients[min_coeffs_len..])
+        }
+
+        if rhs.coefficients.len() > min_coeffs_len {
+            coefficients_diff.extend(
+                rhs.coefficients[min_coeffs_len..]
+                    .iter()
+                    .map(|coeff| -coeff),
+            )
+        }
+
+        Self {
+            coefficients: coefficients_diff,
+        }
+    }
+}
+
+impl SubAssign for Polynomial {
+    fn sub_assign(&mut self, rhs: Self) {
+        *self = self.clone() - rhs;
+    }
+}
+
 impl MulAssign for Polynomial {
     fn mul_assign(&mut self, rhs: Self) {
         *self = self.clone() * rhs;
@@ -228,11 +743,22 @@ impl Div<BaseField> for Polynomial {
     }
 }
 
+impl Div for Polynomial {
+    type Output = Self;
+
+    /// Panics if `rhs` doesn't divide `self` exactly. Use
+    /// `Polynomial::div_exact()` to handle that case.
+    fn div(self, rhs: Self) -> Self::Output {
+        self.div_exact(&rhs)
+            .unwrap_or_else(|err| panic!("polynomial division failed: {err}"))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    use crate::domain::DOMAIN_TRACE;
+    use crate::domain::{DOMAIN_LDE, DOMAIN_TRACE};
 
     #[test]
     pub fn poly_add_self() {
@@ -264,6 +790,49 @@ mod tests {
         )
     }
 
+    #[test]
+    pub fn poly_sub_self() {
+        let poly_1 = Polynomial::new(vec![1.into(), 2.into(), 3.into()]);
+        let poly_2 = poly_1.clone();
+
+        let diff_poly = poly_1 - poly_2;
+
+        assert_eq!(diff_poly.coefficients, vec![0.into(), 0.into(), 0.into()]);
+        assert!(diff_poly.is_zero());
+    }
+
+    #[test]
+    pub fn poly_sub_diff_degree() {
+        let poly_1 = Polynomial::new(vec![1.into(), 2.into(), 3.into()]);
+        let poly_2 = Polynomial::new(vec![0.into(), 5.into(), 0.into(), 4.into(), 6.into()]);
+
+        assert_eq!(
+            (poly_1.clone() - poly_2.clone()).coefficients,
+            vec![1.into(), (-3).into(), 3.into(), (-4).into(), (-6).into()]
+        );
+        assert_eq!(
+            (poly_2 - poly_1).coefficients,
+            vec![(-1).into(), 3.into(), (-3).into(), 4.into(), 6.into()]
+        );
+    }
+
+    #[test]
+    pub fn poly_sub_assign() {
+        let polys = vec![
+            Polynomial::zero(),
+            Polynomial::one(),
+            Polynomial::new(vec![6.into(), 16.into(), 2.into(), 13.into()]),
+            Polynomial::new(vec![(-13).into(), 1.into()]),
+        ];
+
+        for poly in polys {
+            let mut diff_poly = poly.clone();
+            diff_poly -= poly;
+
+            assert!(diff_poly.is_zero());
+        }
+    }
+
     #[test]
     pub fn poly_mul_self() {
         let poly_1 = Polynomial::new(vec![1.into(), 2.into(), 3.into()]);
@@ -336,6 +905,56 @@ mod tests {
         assert_eq!(poly_2.clone(), Polynomial::one() * poly_2);
     }
 
+    #[test]
+    pub fn roots() {
+        // (x - 2)(x - 5) = x^2 - 7x + 10
+        let poly = Polynomial::new(vec![10.into(), -BaseField::from(7), 1.into()]);
+        assert_eq!(poly.roots(), vec![2.into(), 5.into()]);
+
+        assert_eq!(
+            Polynomial::vanishing_polynomial(&DOMAIN_TRACE).roots(),
+            vec![1.into(), 4.into(), 13.into(), 16.into()]
+        );
+
+        // x^2 + 1 has roots, since -1 = 16 = 4^2
+        assert_eq!(
+            Polynomial::new(vec![1.into(), 0.into(), 1.into()]).roots(),
+            vec![4.into(), 13.into()]
+        );
+    }
+
+    #[test]
+    pub fn trim() {
+        assert_eq!(
+            Polynomial::new(vec![3.into(), 0.into()]).trimmed().degree(),
+            0
+        );
+
+        let mut poly = Polynomial::new(vec![1.into(), 0.into(), 2.into(), 0.into(), 0.into()]);
+        poly.trim();
+        assert_eq!(poly, Polynomial::new(vec![1.into(), 0.into(), 2.into()]));
+
+        let zero_poly = Polynomial::new(vec![0.into(), 0.into(), 0.into()]).trimmed();
+        assert_eq!(zero_poly, Polynomial::zero());
+        assert_eq!(zero_poly.degree(), 0);
+    }
+
+    #[test]
+    pub fn product_polynomial_tree() {
+        // x - 1, x - 2, x - 3
+        let polys = [
+            Polynomial::new(vec![(-1).into(), 1.into()]),
+            Polynomial::new(vec![(-2).into(), 1.into()]),
+            Polynomial::new(vec![(-3).into(), 1.into()]),
+        ];
+
+        // (x - 1)(x - 2)(x - 3) = x^3 - 6x^2 + 11x - 6
+        let expected = Polynomial::new(vec![(-6).into(), 11.into(), (-6).into(), 1.into()]);
+
+        assert_eq!(product_polynomial(&polys), expected);
+        assert_eq!(product_polynomial(&[]), Polynomial::one());
+    }
+
     #[test]
     pub fn lagrange_interp() {
         let evaluations: Vec<BaseField> = vec![3.into(), 9.into(), 13.into(), 16.into()];
@@ -350,6 +969,100 @@ mod tests {
         );
     }
 
+    #[test]
+    pub fn check_interpolation_wrong_evaluation() {
+        let evaluations: Vec<BaseField> = vec![3.into(), 9.into(), 13.into(), 16.into()];
+
+        let interp_poly = Polynomial::lagrange_interp(&DOMAIN_TRACE, &evaluations).unwrap();
+        assert!(check_interpolation(&interp_poly, &DOMAIN_TRACE, &evaluations).is_ok());
+
+        let wrong_evaluations: Vec<BaseField> = vec![3.into(), 9.into(), 14.into(), 16.into()];
+        assert!(check_interpolation(&interp_poly, &DOMAIN_TRACE, &wrong_evaluations).is_err());
+    }
+
+    #[test]
+    pub fn neg_x() {
+        let poly = Polynomial::new(vec![7.into(), 3.into(), 4.into(), 5.into()]);
+
+        for x in &DOMAIN_LDE {
+            assert_eq!(poly.neg_x().eval(*x), poly.eval(-*x));
+        }
+
+        // (p(x) + p(-x)) / 2 only keeps the even coefficients
+        let even_poly = (poly.clone() + poly.neg_x()) / BaseField::from(2);
+        assert_eq!(
+            even_poly,
+            Polynomial::new(vec![7.into(), 0.into(), 4.into(), 0.into()])
+        );
+    }
+
+    #[test]
+    pub fn scale_x() {
+        let poly = Polynomial::new(vec![7.into(), 3.into(), 4.into(), 5.into()]);
+        let c = BaseField::from(13);
+
+        for x in &DOMAIN_LDE {
+            assert_eq!(poly.scale_x(c).eval(*x), poly.eval(c * *x));
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "parallel")]
+    pub fn eval_domain_par() {
+        let poly = Polynomial::new(vec![1.into(), 2.into(), 3.into(), 4.into()]);
+
+        assert_eq!(
+            poly.eval_domain_par(&DOMAIN_LDE),
+            poly.eval_domain(&DOMAIN_LDE)
+        );
+    }
+
+    #[test]
+    pub fn dft_idft() {
+        let coefficients: Vec<BaseField> = vec![1.into(), 2.into(), 3.into(), 4.into()];
+
+        // 13 generates DOMAIN_TRACE
+        let evaluations = dft(&coefficients, 13.into());
+        assert_eq!(
+            evaluations,
+            Polynomial::new(coefficients.clone()).eval_domain(&DOMAIN_TRACE)
+        );
+        assert_eq!(idft(&evaluations, 13.into()), coefficients);
+
+        // 3 generates the whole multiplicative group (i.e. the 16th roots of
+        // unity)
+        let coefficients: Vec<BaseField> = (0..16).map(BaseField::from).collect();
+        let evaluations = dft(&coefficients, 3.into());
+
+        let mut ntt_evaluations = coefficients.clone();
+        ntt_forward(&mut ntt_evaluations, 3.into());
+        assert_eq!(evaluations, ntt_evaluations);
+        assert_eq!(idft(&evaluations, 3.into()), coefficients);
+    }
+
+    #[test]
+    pub fn coset_evaluation_matches_eval_domain() {
+        let poly = Polynomial::new(vec![1.into(), 2.into(), 3.into(), 4.into()]);
+
+        assert_eq!(
+            coset_evaluation(&poly, 3.into(), 9.into(), 8),
+            poly.eval_domain(&DOMAIN_LDE)
+        );
+
+        // A polynomial of higher degree than the size of the coset
+        let poly = Polynomial::new((1..=10).map(BaseField::from).collect());
+        assert_eq!(
+            coset_evaluation(&poly, 3.into(), 9.into(), 8),
+            poly.eval_domain(&DOMAIN_LDE)
+        );
+
+        // With a shift of 1, this is the subgroup itself
+        assert_eq!(
+            coset_evaluation(&poly, BaseField::one(), 13.into(), 4),
+            poly.eval_domain(&DOMAIN_TRACE)
+        );
+    }
+
     #[test]
     pub fn fri_step_deg_3() {
         let poly = Polynomial::new(vec![1.into(), 2.into(), 3.into(), 4.into()]);
@@ -372,4 +1085,282 @@ mod tests {
 
         assert_eq!(expected_poly, poly.fri_step(beta));
     }
+
+    #[test]
+    pub fn polynomial_hash_equality() {
+        let poly_1 = Polynomial::new(vec![1.into(), 2.into(), 3.into()]);
+        let poly_2 = poly_1.clone();
+        let poly_3 = Polynomial::new(vec![3.into(), 2.into(), 1.into()]);
+
+        assert_eq!(polynomial_hash(&poly_1), polynomial_hash(&poly_2));
+        assert_ne!(polynomial_hash(&poly_1), polynomial_hash(&poly_3));
+        assert_ne!(
+            polynomial_hash(&Polynomial::zero()),
+            polynomial_hash(&Polynomial::one())
+        );
+    }
+
+    #[test]
+    pub fn lagrange_basis_kronecker_delta() {
+        let basis = Polynomial::lagrange_basis(&DOMAIN_TRACE).unwrap();
+
+        assert_eq!(basis.len(), DOMAIN_TRACE.len());
+        for (i, basis_poly) in basis.iter().enumerate() {
+            for (j, x) in DOMAIN_TRACE.iter().enumerate() {
+                let expected = if i == j {
+                    BaseField::one()
+                } else {
+                    BaseField::zero()
+                };
+
+                assert_eq!(basis_poly.eval(*x), expected);
+            }
+        }
+    }
+
+    #[test]
+    pub fn lagrange_basis_partition_of_unity() {
+        let basis_sum: Polynomial = Polynomial::lagrange_basis(&DOMAIN_TRACE)
+            .unwrap()
+            .into_iter()
+            .sum();
+
+        assert_eq!(
+            basis_sum,
+            Polynomial::new(vec![1.into(), 0.into(), 0.into(), 0.into()])
+        );
+    }
+
+    #[test]
+    pub fn lagrange_basis_duplicate_element() {
+        let domain: Vec<BaseField> = vec![1.into(), 2.into(), 1.into()];
+
+        assert!(Polynomial::lagrange_basis(&domain).is_err());
+    }
+
+    #[test]
+    pub fn poly_div_reconstructs_dividend() {
+        // 5x^3 + 4x^2 + 3x + 7
+        let dividend = Polynomial::new(vec![7.into(), 3.into(), 4.into(), 5.into()]);
+        // 2x + 1
+        let divisor = Polynomial::new(vec![1.into(), 2.into()]);
+
+        let (quotient, remainder) = poly_div(&dividend, &divisor).unwrap();
+
+        assert_eq!(quotient.degree(), 2);
+        assert_eq!(remainder.degree(), 0);
+        check_polynomial_identity_over_domain(
+            &dividend,
+            &(quotient * divisor + remainder),
+            &(0..17).map(BaseField::from).collect::<Vec<_>>(),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    pub fn poly_div_by_zero() {
+        let dividend = Polynomial::new(vec![7.into(), 3.into()]);
+
+        assert!(poly_div(&dividend, &Polynomial::zero()).is_err());
+    }
+
+    #[test]
+    pub fn vanishes_on_domain_fast_matches_evaluation() {
+        let vanishing_poly = Polynomial::vanishing_polynomial(&DOMAIN_TRACE);
+
+        let polys = vec![
+            Polynomial::zero(),
+            Polynomial::one(),
+            vanishing_poly.clone(),
+            vanishing_poly.clone() * Polynomial::new(vec![3.into(), 5.into()]),
+            vanishing_poly + Polynomial::one(),
+            Polynomial::new(vec![6.into(), 16.into(), 2.into(), 13.into()]),
+            Polynomial::new(vec![16.into(), 0.into(), 0.into(), 0.into(), 1.into()]),
+        ];
+
+        for poly in polys {
+            let vanishes_by_eval = poly
+                .eval_domain(&DOMAIN_TRACE)
+                .iter()
+                .all(|y| *y == BaseField::zero());
+
+            assert_eq!(
+                vanishes_on_domain_fast(&poly, &DOMAIN_TRACE),
+                vanishes_by_eval,
+                "poly: {poly:?}"
+            );
+        }
+    }
+
+    #[test]
+    pub fn lagrange_value_at_matches_interp() {
+        let evaluations: Vec<BaseField> = vec![3.into(), 9.into(), 13.into(), 16.into()];
+        let interp_poly = Polynomial::lagrange_interp(&DOMAIN_TRACE, &evaluations).unwrap();
+
+        // Points inside the domain
+        for (x, y) in DOMAIN_TRACE.iter().zip(evaluations.iter()) {
+            assert_eq!(
+                lagrange_value_at(&DOMAIN_TRACE, &evaluations, *x).unwrap(),
+                *y
+            );
+        }
+
+        // Points outside the domain
+        for x in &DOMAIN_LDE {
+            assert_eq!(
+                lagrange_value_at(&DOMAIN_TRACE, &evaluations, *x).unwrap(),
+                interp_poly.eval(*x)
+            );
+        }
+
+        assert!(lagrange_value_at(&DOMAIN_TRACE, &evaluations[0..2], 0.into()).is_err());
+    }
+
+    #[test]
+    pub fn sum_over_cyclic_domain() {
+        let poly = Polynomial::new(vec![6.into(), 16.into(), 2.into(), 13.into()]);
+
+        let sum_by_eval = poly
+            .eval_domain(&DOMAIN_LDE)
+            .into_iter()
+            .fold(BaseField::zero(), |acc, y| acc + y);
+        assert_eq!(sum_over_domain(&poly, &DOMAIN_LDE), sum_by_eval);
+
+        // deg(poly) = 3, which is less than the size of both domains
+        assert_eq!(
+            sum_over_domain(&poly, &DOMAIN_TRACE),
+            BaseField::from(4) * BaseField::from(6)
+        );
+        assert_eq!(
+            sum_over_domain(&poly, &DOMAIN_LDE),
+            BaseField::from(8) * BaseField::from(6)
+        );
+    }
+
+    #[test]
+    pub fn div_exact() {
+        // x^2 - 1
+        let dividend = Polynomial::new(vec![(-1).into(), 0.into(), 1.into()]);
+        // x - 1
+        let divisor = Polynomial::new(vec![(-1).into(), 1.into()]);
+
+        // x + 1
+        let expected_quotient = Polynomial::new(vec![1.into(), 1.into()]);
+
+        assert_eq!(dividend.div_exact(&divisor).unwrap(), expected_quotient);
+        assert_eq!(dividend / divisor, expected_quotient);
+    }
+
+    #[test]
+    pub fn div_exact_rejects_non_exact_divisor() {
+        // x^2 + 1
+        let dividend = Polynomial::new(vec![1.into(), 0.into(), 1.into()]);
+        // x - 1
+        let divisor = Polynomial::new(vec![(-1).into(), 1.into()]);
+
+        assert!(dividend.div_exact(&divisor).is_err());
+        assert!(dividend.div_exact(&Polynomial::zero()).is_err());
+    }
+
+    #[test]
+    pub fn split_into_k_parts() {
+        // 5x^3 + 4x^2 + 3x + 7
+        let poly = Polynomial::new(vec![7.into(), 3.into(), 4.into(), 5.into()]);
+
+        assert_eq!(
+            poly.split_into_k_parts(2),
+            vec![
+                Polynomial::new(vec![7.into(), 4.into()]),
+                Polynomial::new(vec![3.into(), 5.into()])
+            ]
+        );
+        assert_eq!(
+            poly.split_into_k_parts(4),
+            [7, 3, 4, 5].map(|coeff| Polynomial::new(vec![coeff.into()]))
+        );
+        assert_eq!(poly.split_into_k_parts(8)[5], Polynomial::zero());
+
+        // p(x) = sum_j x^j * p_j(x^k)
+        for k in 1..=5 {
+            let parts = poly.split_into_k_parts(k);
+            for x in &DOMAIN_LDE {
+                let recombined = parts
+                    .iter()
+                    .enumerate()
+                    .map(|(j, part)| x.exp(j as u8) * part.eval(x.exp(k as u8)))
+                    .fold(BaseField::zero(), |acc, term| acc + term);
+
+                assert_eq!(recombined, poly.eval(*x));
+            }
+        }
+    }
+
+    #[test]
+    pub fn div_by_linear_matches_poly_div() {
+        let poly = Polynomial::new(vec![3.into(), 0.into(), 5.into(), 16.into(), 1.into()]);
+
+        for z in 0..17u8 {
+            let z = BaseField::from(z);
+            let (quotient, remainder) = poly.div_by_linear(z);
+            let (expected_quotient, expected_remainder) =
+                poly_div(&poly, &Polynomial::new(vec![-z, 1.into()])).unwrap();
+
+            assert_eq!(quotient, expected_quotient);
+            assert_eq!(Polynomial::new(vec![remainder]), expected_remainder);
+            assert_eq!(remainder, poly.eval(z));
+        }
+
+        let constant = Polynomial::new(vec![7.into()]);
+        assert_eq!(
+            constant.div_by_linear(3.into()),
+            (Polynomial::zero(), BaseField::from(7))
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    pub fn div_panics_on_non_exact_divisor() {
+        // x^2 + 1
+        let dividend = Polynomial::new(vec![1.into(), 0.into(), 1.into()]);
+        // x - 1
+        let divisor = Polynomial::new(vec![(-1).into(), 1.into()]);
+
+        let _ = dividend / divisor;
+    }
+
+    #[test]
+    pub fn eval_at_generator_power_agrees_with_eval() {
+        let poly = Polynomial::new(vec![3.into(), 0.into(), 5.into(), 16.into(), 1.into()]);
+        let generator = DOMAIN_TRACE[1];
+
+        for k in 0..40 {
+            assert_eq!(
+                eval_at_generator_power(&poly, generator, k),
+                poly.eval(generator.exp(k as u8))
+            );
+        }
+    }
+
+    #[test]
+    pub fn all_polynomials_of_degree_at_most_0() {
+        let polys: Vec<Polynomial> = all_polynomials_of_degree_at_most(0).collect();
+        assert_eq!(polys.len(), 17);
+
+        for (i, poly) in polys.iter().enumerate() {
+            assert_eq!(*poly, Polynomial::new(vec![BaseField::from(i as u8)]));
+        }
+    }
+
+    #[test]
+    pub fn all_polynomials_of_degree_at_most_2() {
+        let polys: Vec<Polynomial> = all_polynomials_of_degree_at_most(2).collect();
+        assert_eq!(polys.len(), 17 * 17 * 17);
+        assert!(polys.iter().all(|poly| poly.degree() <= 2));
+
+        let distinct: std::collections::HashSet<Vec<BaseField>> = polys
+            .iter()
+            .map(|poly| poly.coefficients().to_vec())
+            .collect();
+        assert_eq!(distinct.len(), polys.len());
+    }
 }
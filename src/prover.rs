@@ -1,66 +1,326 @@
+use std::fmt::Display;
+
 use crate::{
-    channel::Channel,
-    constraints::composition_polynomial,
+    channel::{Channel, ChannelTranscript},
+    constraints::{BoundaryConstraint, ConstraintSystem, TransitionConstraint},
     domain::{DOMAIN_LDE, DOMAIN_TRACE},
     field::BaseField,
+    fri::{fri_optimal_queries, fri_step, generate_fri_proof, FriLayer, SecurityLevel},
+    hash::HashAlgorithm,
     merkle::{MerklePath, MerkleTree},
-    poly::Polynomial,
-    trace::generate_trace,
+    trace::{generate_trace, Trace, TRACE_FIRST_ELEMENT},
+    verifier::{verify, VerifierConfig},
     ProofQueryPhase, StarkProof,
 };
 
-pub fn generate_proof() -> StarkProof {
-    let mut channel = Channel::new();
+/// The reasons why the prover can fail to generate a proof
+#[derive(Debug)]
+pub enum ProverError {
+    /// The trace doesn't satisfy the constraints of the statement to prove.
+    TraceConstraintViolated,
+    /// The last FRI layer doesn't evaluate to the same value everywhere (i.e.
+    /// the composition polynomial has a higher degree than expected).
+    FriFinalLayerInconsistent,
+    /// The trace, LDE domain or blowup factor don't agree on the size of the
+    /// domains.
+    DomainSizeMismatch,
+    /// The prover requested a configuration that it doesn't support.
+    UnsupportedConfig,
+    /// The Merkle path of a queried value (in the trace or in a FRI layer)
+    /// couldn't be constructed, e.g. because the query index is out of
+    /// bounds. Wraps the underlying error.
+    MerklePathConstructionFailed(anyhow::Error),
+}
+
+impl Display for ProverError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProverError::TraceConstraintViolated => write!(f, "trace violates the constraints"),
+            ProverError::FriFinalLayerInconsistent => {
+                write!(f, "last FRI layer is not a constant")
+            }
+            ProverError::DomainSizeMismatch => write!(f, "domain sizes don't match"),
+            ProverError::UnsupportedConfig => write!(f, "unsupported prover configuration"),
+            ProverError::MerklePathConstructionFailed(err) => {
+                write!(f, "failed to construct the Merkle path of a query: {err}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ProverError {}
+
+/// Generates the proof, and verifies it before returning it. This is a sanity
+/// check that the prover is behaving correctly.
+pub fn generate_proof_verified(pub_inputs: &[u8]) -> anyhow::Result<StarkProof> {
+    generate_proof_with_options(pub_inputs, true)
+}
+
+/// Generates the proof, and verifies it before returning it if `verify_after`
+/// is set.
+pub fn generate_proof_with_options(
+    pub_inputs: &[u8],
+    verify_after: bool,
+) -> anyhow::Result<StarkProof> {
+    let proof = generate_proof_with_config(pub_inputs, &ProverConfig::default())?;
+
+    if verify_after {
+        verify(&proof, pub_inputs)?;
+    }
+
+    Ok(proof)
+}
+
+/// The maximum value of `ProverConfig::grinding_bits`. Grinding takes `2^bits`
+/// hashes on average, so anything larger would take forever.
+pub const MAX_GRINDING_BITS: u32 = 32;
+
+/// Parameters of the proof generation
+#[derive(Clone, Debug)]
+pub struct ProverConfig {
+    /// The number of queries to include in the proof. Each query is checked
+    /// independently by the verifier, so the probability that a cheating
+    /// prover gets away with it decreases exponentially with the number of
+    /// queries: doubling `num_queries` squares the soundness error (i.e.
+    /// doubles the bits of security). The price to pay is a proof (and
+    /// verification time) that grows linearly with the number of queries.
+    /// Must be at least 1, and must match the verifier's
+    /// `VerifierConfig::num_queries`.
+    pub num_queries: usize,
+    /// The ratio of the size of the LDE domain to the size of the trace
+    /// domain. Our domains are fixed (see `DOMAIN_LDE`), so this must be 2.
+    pub blowup_factor: usize,
+    /// The number of bits of proof-of-work the prover must do before drawing
+    /// the query indices (see `Channel::grind()`). Each bit doubles the work of
+    /// a cheating prover, which adds one bit of security without making the
+    /// proof any bigger. Must be at most `MAX_GRINDING_BITS`.
+    pub grinding_bits: u32,
+    /// The hash function used for the Merkle trees and the channel. It is
+    /// recorded in the proof, so that the verifier uses the same one.
+    pub hash_algorithm: HashAlgorithm,
+}
+
+impl Default for ProverConfig {
+    fn default() -> Self {
+        Self {
+            num_queries: 1,
+            blowup_factor: 2,
+            grinding_bits: 0,
+            hash_algorithm: HashAlgorithm::Blake3,
+        }
+    }
+}
+
+impl ProverConfig {
+    /// Returns the default configuration, with `blowup` and enough queries
+    /// for `target_bits` bits of security (see `fri_optimal_queries()`, which
+    /// also explains why the field of this crate can't actually provide much
+    /// security).
+    ///
+    /// Fails with `ProverError::DomainSizeMismatch` if `blowup` isn't the one
+    /// of our domains (see `ProverConfig::blowup_factor`).
+    pub fn with_security_level(
+        target_bits: u32,
+        trace_degree: usize,
+        blowup: usize,
+    ) -> Result<Self, ProverError> {
+        if blowup != DOMAIN_LDE.len() / DOMAIN_TRACE.len() {
+            return Err(ProverError::DomainSizeMismatch);
+        }
+
+        Ok(Self {
+            num_queries: fri_optimal_queries(trace_degree, blowup, target_bits),
+            blowup_factor: blowup,
+            ..Default::default()
+        })
+    }
+
+    /// Same as `with_security_level()`, for one of the usual security levels
+    pub fn with_security(
+        level: SecurityLevel,
+        degree: usize,
+        blowup: usize,
+    ) -> Result<Self, ProverError> {
+        Self::with_security_level(level.bits(), degree, blowup)
+    }
+}
+
+impl From<&ProverConfig> for VerifierConfig {
+    /// The verifier configuration that accepts proofs generated with `config`
+    fn from(config: &ProverConfig) -> Self {
+        Self {
+            num_queries: config.num_queries,
+            grinding_bits: config.grinding_bits,
+        }
+    }
+}
+
+/// Approximate number of operations performed by the prover. See
+/// `estimate_prover_work()`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProverWorkEstimate {
+    /// Field multiplications to interpolate the trace, and evaluate it over
+    /// the LDE domain
+    pub lagrange_interp_ops: usize,
+    /// Hashes to build every Merkle tree
+    pub merkle_hash_ops: usize,
+    /// Field operations to fold the FRI layers, and answer the queries
+    pub fri_ops: usize,
+}
+
+/// Estimates the work done by the prover for a trace of length `trace_len`
+/// (assumed to be a power of 2), using the known complexities of each step:
+///
+/// - Lagrange interpolation builds `n` basis polynomials, each a product of
+///   `n - 1` linear factors, for a total of `O(n^3)`. Evaluating the result
+///   over the LDE domain is another `n` multiplications per point.
+/// - A Merkle tree over `m` leaves has `2m - 1` nodes, each hashed once. We
+///   commit to the trace LDE, the composition polynomial LDE, and every FRI
+///   layer but the last (which is sent in the clear).
+/// - Each FRI fold does a constant amount of work per element of the next
+///   domain, and each query does a constant amount of work per layer.
+pub fn estimate_prover_work(
+    trace_len: usize,
+    blowup_factor: usize,
+    num_queries: usize,
+) -> ProverWorkEstimate {
+    let lde_len = trace_len * blowup_factor;
+    // Folding a polynomial of degree `trace_len - 1` down to a constant
+    let num_fri_layers = trace_len.trailing_zeros() as usize;
+    let fri_layer_sizes = (1..=num_fri_layers).map(|i| lde_len >> i);
+
+    let lagrange_interp_ops = trace_len.pow(3) + lde_len * trace_len;
+
+    let merkle_tree_size = |num_leaves: usize| 2 * num_leaves - 1;
+    let merkle_hash_ops = 2 * merkle_tree_size(lde_len)
+        + fri_layer_sizes
+            .clone()
+            .take(num_fri_layers.saturating_sub(1))
+            .map(merkle_tree_size)
+            .sum::<usize>();
+
+    let fri_ops = fri_layer_sizes.sum::<usize>() + num_queries * num_fri_layers;
+
+    ProverWorkEstimate {
+        lagrange_interp_ops,
+        merkle_hash_ops,
+        fri_ops,
+    }
+}
+
+/// Generates the proof with the default `ProverConfig`. The channel is seeded
+/// with `pub_inputs`, which binds the proof to them; the verifier must be
+/// given the same public inputs.
+///
+/// Panics if the proof generation fails, which never happens with the default
+/// configuration.
+pub fn generate_proof(pub_inputs: &[u8]) -> StarkProof {
+    generate_proof_with_config(pub_inputs, &ProverConfig::default())
+        .expect("default configuration is supported")
+}
+
+pub fn generate_proof_with_config(
+    pub_inputs: &[u8],
+    config: &ProverConfig,
+) -> Result<StarkProof, ProverError> {
+    generate_proof_for_trace(&generate_trace(), TRACE_FIRST_ELEMENT, pub_inputs, config)
+}
+
+/// Generates the proof that `trace` starts with `first_element`, and that
+/// every element is the square of the previous one. Verify it with
+/// `verifier::verify_with_first_element()`.
+pub fn generate_proof_for_trace(
+    trace: &Trace,
+    first_element: BaseField,
+    pub_inputs: &[u8],
+    config: &ProverConfig,
+) -> Result<StarkProof, ProverError> {
+    let channel = Channel::new_with_hash(config.hash_algorithm, pub_inputs);
+
+    generate_proof_with_channel(trace, first_element, config, channel).map(|(proof, _)| proof)
+}
+
+/// Generates the proof with the default `ProverConfig`, along with the
+/// transcript of the prover's channel. Replaying the transcript against the
+/// verifier's channel shows where the two diverge, if they do.
+pub fn generate_proof_with_transcript(pub_inputs: &[u8]) -> (StarkProof, ChannelTranscript) {
+    let (proof, transcript) = generate_proof_with_channel(
+        &generate_trace(),
+        TRACE_FIRST_ELEMENT,
+        &ProverConfig::default(),
+        Channel::new_recording(pub_inputs),
+    )
+    .expect("default configuration is supported");
+
+    (proof, transcript.expect("channel is recording"))
+}
+
+/// Generates the proof using `channel` as the source of randomness. Returns the
+/// transcript of the channel along with the proof, if the channel is recording.
+///
+/// Precondition: `channel` uses `config.hash_algorithm`
+fn generate_proof_with_channel(
+    trace: &Trace,
+    first_element: BaseField,
+    config: &ProverConfig,
+    mut channel: Channel,
+) -> Result<(StarkProof, Option<ChannelTranscript>), ProverError> {
+    assert_eq!(channel.hash_algorithm(), config.hash_algorithm);
+
+    if config.blowup_factor != DOMAIN_LDE.len() / DOMAIN_TRACE.len() {
+        return Err(ProverError::DomainSizeMismatch);
+    }
+    // A proof without queries can't convince the verifier of anything
+    if config.num_queries == 0 || config.grinding_bits > MAX_GRINDING_BITS {
+        return Err(ProverError::UnsupportedConfig);
+    }
 
     ////////////////////
     // Commitment phase
     ////////////////////
 
     // Trace
-    let trace = generate_trace();
-    let trace_polynomial = Polynomial::lagrange_interp(&DOMAIN_TRACE, &trace).unwrap();
+    let constraint_system = {
+        let mut constraint_system = ConstraintSystem::new();
+        constraint_system.add(BoundaryConstraint {
+            column: 0,
+            first_element,
+        });
+        constraint_system.add(TransitionConstraint { column: 0 });
+
+        constraint_system
+    };
+    constraint_system
+        .check_trace(trace)
+        .map_err(|_| ProverError::TraceConstraintViolated)?;
 
-    let trace_lde = trace_polynomial.eval_domain(&DOMAIN_LDE);
-    let trace_lde_merkleized = MerkleTree::new(&trace_lde);
+    let trace_lde = trace
+        .extend_lde(&DOMAIN_LDE)
+        .map_err(|_| ProverError::DomainSizeMismatch)?;
+    let trace_lde = trace_lde.column(0);
+    let trace_lde_merkleized = MerkleTree::new_with_hash(trace_lde, channel.hash_algorithm());
 
     channel.commit(trace_lde_merkleized.root);
 
     // Composition polynomial
-    let cp = {
-        let alpha_0 = channel.random_element();
-        let alpha_1 = channel.random_element();
-
-        composition_polynomial(alpha_0, alpha_1)
-    };
+    let cp = constraint_system.compose_random(trace, &mut channel);
 
-    let cp_lde = cp.eval_domain(&DOMAIN_LDE);
-    let cp_lde_merkleized = MerkleTree::new(&cp_lde);
+    // The composition polynomial over the LDE domain is the first FRI layer
+    let cp_layer = FriLayer::new((&DOMAIN_LDE).into(), cp, channel.hash_algorithm());
 
-    channel.commit(cp_lde_merkleized.root);
+    channel.commit(cp_layer.merkle_tree.root);
 
     // FRI
     let beta_fri_deg_1 = channel.random_element();
-    let (domain_deg_1, fri_layer_deg_1_poly) = fri_step(&DOMAIN_LDE, cp.clone(), beta_fri_deg_1);
-    let fri_layer_deg_1_eval = fri_layer_deg_1_poly.eval_domain(&domain_deg_1);
-    let fri_layer_deg_1_merkleized = MerkleTree::new(&fri_layer_deg_1_eval);
-
-    channel.commit(fri_layer_deg_1_merkleized.root);
+    let fri_layer_deg_1 = commit_to_fri_layer(&cp_layer, beta_fri_deg_1, &mut channel);
 
     let beta_fri_deg_0 = channel.random_element();
-    let (domain_deg_0, fri_layer_deg_0_poly) =
-        fri_step(&domain_deg_1, fri_layer_deg_1_poly.clone(), beta_fri_deg_0);
-
-    // The last layer has degree 0, with 2 elements. Therefore, we expect both
-    // of these elements to be the same value (a degree 0 polynomial is a
-    // constant function, meaning that it evaluates to the same value
-    // everywhere).
-    assert_eq!(domain_deg_0.len(), 2);
-    assert_eq!(
-        fri_layer_deg_0_poly.eval(domain_deg_0[0]),
-        fri_layer_deg_0_poly.eval(domain_deg_0[1])
-    );
+    let fri_layer_deg_0 = fri_step(&fri_layer_deg_1, beta_fri_deg_0);
+
+    // The value of the last layer is sent in the clear, so it must be constant
+    fri_final_layer_value(&fri_layer_deg_0)?;
 
-    let fri_layer_deg_0_eval = fri_layer_deg_0_poly.eval(domain_deg_0[0]);
+    let fri_layers = [cp_layer, fri_layer_deg_1, fri_layer_deg_0];
 
     ////////////////////
     // Query phase
@@ -68,7 +328,7 @@ pub fn generate_proof() -> StarkProof {
 
     // Note: We will need to send (extended) trace elements at index i and i+2.
     // Since our (extended) trace has 8 elements, we draw i to be between [0,
-    // 7].
+    // 5], such that i+2 is still in the trace.
     //
     // Let's see why that is. Let g be the generator of the trace domain (size
     // of 4), and w be the generator of the LDE domain (size of 8). We know g=13
@@ -77,20 +337,24 @@ pub fn generate_proof() -> StarkProof {
     // the coset (see `CyclicGroup`). We want to know the index of `t(g *
     // hw^i)`. We have that `t(ghw^i) = t(w^2 * h * w^i) = t(h * w^(i+2))`, so
     // the index is `i+2`.
+    //
+    // Every query index is drawn independently from the channel, after the
+    // proof-of-work.
+    let grind_nonce = (config.grinding_bits > 0).then(|| channel.grind(config.grinding_bits));
 
-    let query_idx = channel.random_integer(DOMAIN_LDE.len() as u8 - 2) as usize;
-
-    let query_phase = generate_query_phase(
-        query_idx,
-        &trace_lde,
-        &trace_lde_merkleized,
-        &cp_lde,
-        &cp_lde_merkleized,
-        &fri_layer_deg_1_eval,
-        &fri_layer_deg_1_merkleized,
-        fri_layer_deg_0_eval,
-    );
+    let query_indices: Vec<usize> = (0..config.num_queries)
+        .map(|_| channel.random_u64(DOMAIN_LDE.len() as u64 - 2) as usize)
+        .collect();
+
+    let query_phase = query_indices
+        .iter()
+        .map(|&query_idx| generate_query_phase(query_idx, trace_lde, &trace_lde_merkleized))
+        .collect::<Result<_, _>>()?;
+
+    let fri = generate_fri_proof(&fri_layers, &query_indices)
+        .map_err(ProverError::MerklePathConstructionFailed)?;
 
+    let transcript = channel.transcript().cloned();
     let commitments = channel.finalize();
     assert_eq!(
         commitments.len(),
@@ -99,91 +363,399 @@ pub fn generate_proof() -> StarkProof {
         commitments.len()
     );
 
-    StarkProof {
+    let proof = StarkProof {
+        hash_algorithm: config.hash_algorithm,
         trace_lde_commitment: commitments[0],
         composition_poly_lde_commitment: commitments[1],
-        fri_layer_deg_1_commitment: commitments[2],
+        fri,
+        grind_nonce,
         query_phase,
+    };
+
+    Ok((proof, transcript))
+}
+
+/// Returns the value of the last FRI layer.
+///
+/// The last layer has degree 0, with 2 elements. Therefore, we expect both of
+/// these elements to be the same value (a degree 0 polynomial is a constant
+/// function, meaning that it evaluates to the same value everywhere).
+fn fri_final_layer_value(layer: &FriLayer) -> Result<BaseField, ProverError> {
+    let [value, other_value] = layer.evaluations[..] else {
+        return Err(ProverError::DomainSizeMismatch);
+    };
+
+    if value != other_value {
+        return Err(ProverError::FriFinalLayerInconsistent);
     }
+
+    Ok(value)
 }
 
-// Returns the domain and polynomial of the next FRI layer
-fn fri_step(
-    domain: &[BaseField],
-    polynomial: Polynomial,
-    beta: BaseField,
-) -> (Vec<BaseField>, Polynomial) {
-    // The domain of the next FRI layer is (the first or second) half of the
-    // current domain, where every element is squared. Both the first or second
-    // half squared result in the same domain. For example, given a domain with generator g,
-    //
-    // dom = {g^0, g^1, g^2, g^3}
-    // first_half = {g^0, g^1}
-    // first_half_squared = {g^0, g^2}
-    //
-    // second_half = {g^2, g^3}
-    // second_half_squared = {g^4, g^6} = {g^0, g^2}
-    // ^ The second equality is true because g^4 = 1 (by definition of g being the generator)
-    //
-    // Refer to Stark 101 part 3 for more information.
-    let next_domain = domain[0..domain.len() / 2]
-        .iter()
-        .map(|x| x.exp(2))
-        .collect();
+/// Computes the next FRI layer from `layer`, and commits to its evaluations
+fn commit_to_fri_layer(layer: &FriLayer, beta: BaseField, channel: &mut Channel) -> FriLayer {
+    let next_layer = fri_step(layer, beta);
+
+    channel.commit(next_layer.merkle_tree.root);
 
-    (next_domain, polynomial.fri_step(beta))
+    next_layer
 }
 
 /// For an in-depth discussion of how we compute indices in this function, see
-/// the README's section "Prover query phase: computing the correct indices"
-#[allow(clippy::too_many_arguments)]
+/// the README's section "Prover query phase: computing the correct indices".
+/// The values of the composition polynomial and FRI layers are opened
+/// separately, by `generate_fri_proof()`.
 fn generate_query_phase(
     query_idx: usize,
     trace_lde: &[BaseField],
     trace_lde_merkleized: &MerkleTree,
-    cp_lde: &[BaseField],
-    cp_lde_merkleized: &MerkleTree,
-    fri_layer_deg_1_eval: &[BaseField],
-    fri_layer_deg_1_merkleized: &MerkleTree,
-    fri_layer_deg_0_eval: BaseField,
-) -> ProofQueryPhase {
+) -> Result<ProofQueryPhase, ProverError> {
     let t_x = trace_lde[query_idx];
     let t_x_proof = MerklePath::new(trace_lde_merkleized, query_idx)
-        .expect("query index is between 0 and 5, and Merkle tree has 8 elements");
+        .map_err(ProverError::MerklePathConstructionFailed)?;
 
     let t_gx = trace_lde[query_idx + 2];
     let t_gx_proof = MerklePath::new(trace_lde_merkleized, query_idx + 2)
-        .expect("query index is between 2 and 7, and Merkle tree has 8 elements");
+        .map_err(ProverError::MerklePathConstructionFailed)?;
 
-    // Query composition polynomial (domain size = 8)
-    let (cp_minus_x, cp_minus_x_proof) = {
-        let domain_len = DOMAIN_LDE.len();
-        let query_idx_minus_x = (query_idx + domain_len / 2) % domain_len;
+    Ok(ProofQueryPhase {
+        trace_x: (t_x, t_x_proof),
+        trace_gx: (t_gx, t_gx_proof),
+    })
+}
 
-        (
-            cp_lde[query_idx_minus_x],
-            MerklePath::new(cp_lde_merkleized, query_idx_minus_x).unwrap(),
-        )
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        channel::TranscriptMessage,
+        domain::DynamicDomain,
+        poly::Polynomial,
+        verifier::{verify_with_config, VerificationError},
     };
 
-    // Query FRI layer of degree 1 (domain size = 4)
-    let domain_len_fri_1 = DOMAIN_LDE.len() / 2;
-    let query_idx_fri_1_x = query_idx % domain_len_fri_1;
+    #[test]
+    pub fn transcript_replays_in_verifier_channel() {
+        let (proof, transcript) = generate_proof_with_transcript(&[]);
+        assert!(verify(&proof, &[]).is_ok());
+
+        // Replay the prover's messages in a channel set up like the verifier's:
+        // every challenge drawn must match the prover's.
+        let mut verifier_channel = Channel::new_recording(&[]);
+        for (i, message) in transcript.messages.iter().enumerate() {
+            match message {
+                TranscriptMessage::Commit(commitment) => verifier_channel.commit(*commitment),
+                TranscriptMessage::Challenge(element) => {
+                    assert_eq!(verifier_channel.random_element(), *element, "message {i}")
+                }
+                TranscriptMessage::Integer(integer) => assert_eq!(
+                    verifier_channel.random_u64(DOMAIN_LDE.len() as u64 - 2),
+                    *integer,
+                    "message {i}"
+                ),
+                TranscriptMessage::Bytes(bytes) => assert_eq!(
+                    verifier_channel.random_bytes(bytes.len()),
+                    *bytes,
+                    "message {i}"
+                ),
+            }
+        }
+
+        assert_eq!(
+            transcript.first_divergence(verifier_channel.transcript().unwrap()),
+            None
+        );
+        assert_eq!(
+            verifier_channel.finalize(),
+            vec![
+                proof.trace_lde_commitment,
+                proof.composition_poly_lde_commitment,
+                proof.fri.commitment.layer_roots[0]
+            ]
+        );
+    }
+
+    #[test]
+    pub fn commit_to_fri_layer_matches_inline() {
+        let poly = Polynomial::new(vec![1.into(), 2.into(), 3.into(), 4.into()]);
+        let beta = BaseField::from(7);
+
+        let layer = FriLayer::new((&DOMAIN_LDE).into(), poly.clone(), HashAlgorithm::Blake3);
+
+        let mut channel = Channel::new_empty();
+        let next_layer = commit_to_fri_layer(&layer, beta, &mut channel);
+
+        let mut inline_channel = Channel::new_empty();
+        let inline_domain = DynamicDomain::from(&DOMAIN_LDE).halve();
+        let inline_poly = poly.fri_step(beta);
+        let inline_eval = inline_poly.eval_domain(&inline_domain);
+        let inline_merkleized = MerkleTree::new(&inline_eval);
+        inline_channel.commit(inline_merkleized.root);
+
+        assert_eq!(next_layer.domain, inline_domain);
+        assert_eq!(next_layer.polynomial, inline_poly);
+        assert_eq!(next_layer.evaluations, inline_eval);
+        assert_eq!(next_layer.merkle_tree.root, inline_merkleized.root);
+        assert_eq!(channel.random_element(), inline_channel.random_element());
+    }
+
+    #[test]
+    pub fn generate_proof_verified_is_ok() {
+        let proof = generate_proof_verified(&[]).unwrap();
+        assert!(verify(&proof, &[]).is_ok());
+
+        assert!(generate_proof_with_options(&[], false).is_ok());
+    }
 
-    let (fri_layer_deg_1_minus_x, fri_layer_deg_1_minus_x_proof) = {
-        let query_idx_fri_1_minus_x = (query_idx_fri_1_x + domain_len_fri_1 / 2) % domain_len_fri_1;
+    #[test]
+    pub fn multiple_queries() {
+        let config = ProverConfig {
+            num_queries: 3,
+            ..Default::default()
+        };
+        let proof = generate_proof_with_config(&[], &config).unwrap();
 
-        (
-            fri_layer_deg_1_eval[query_idx_fri_1_minus_x],
-            MerklePath::new(fri_layer_deg_1_merkleized, query_idx_fri_1_minus_x).unwrap(),
+        assert_eq!(proof.query_phase.len(), 3);
+        assert!(verify_with_config(&proof, &[], &VerifierConfig::from(&config)).is_ok());
+
+        // The verifier requires its own number of queries: it doesn't accept
+        // fewer (nor more) queries than it asks for
+        assert_eq!(
+            verify(&proof, &[]),
+            Err(VerificationError::QueryIndexMismatch)
+        );
+
+        let mut truncated_proof = proof.clone();
+        truncated_proof.query_phase.truncate(1);
+        truncated_proof.fri.query_paths.truncate(1);
+        assert_eq!(
+            verify_with_config(&truncated_proof, &[], &VerifierConfig::from(&config)),
+            Err(VerificationError::QueryIndexMismatch)
+        );
+    }
+
+    #[test]
+    pub fn tampered_query_fails() {
+        let proof = generate_proof_with_config(
+            &[],
+            &ProverConfig {
+                num_queries: 3,
+                ..Default::default()
+            },
         )
-    };
+        .unwrap();
 
-    ProofQueryPhase {
-        trace_x: (t_x, t_x_proof),
-        trace_gx: (t_gx, t_gx_proof),
-        cp_minus_x: (cp_minus_x, cp_minus_x_proof),
-        fri_layer_deg_1_minus_x: (fri_layer_deg_1_minus_x, fri_layer_deg_1_minus_x_proof),
-        fri_layer_deg_0_x: fri_layer_deg_0_eval,
+        for i in 0..proof.query_phase.len() {
+            let mut tampered_proof = proof.clone();
+            tampered_proof.query_phase[i].trace_x.0 += BaseField::one();
+            assert!(
+                verify_with_config(
+                    &tampered_proof,
+                    &[],
+                    &VerifierConfig {
+                        num_queries: 3,
+                        ..Default::default()
+                    }
+                )
+                .is_err(),
+                "tampered query {i}"
+            );
+
+            let mut tampered_proof = proof.clone();
+            tampered_proof.fri.query_paths[i].last_mut().unwrap().0 += BaseField::one();
+            assert!(
+                verify_with_config(
+                    &tampered_proof,
+                    &[],
+                    &VerifierConfig {
+                        num_queries: 3,
+                        ..Default::default()
+                    }
+                )
+                .is_err(),
+                "tampered query {i}"
+            );
+        }
+    }
+
+    #[test]
+    pub fn with_security_level() {
+        let config = ProverConfig::with_security_level(80, DOMAIN_TRACE.len() - 1, 2).unwrap();
+        assert!(config.num_queries >= 40);
+        assert_eq!(config.blowup_factor, 2);
+
+        assert_eq!(
+            ProverConfig::with_security(SecurityLevel::Bits80, DOMAIN_TRACE.len() - 1, 2)
+                .unwrap()
+                .num_queries,
+            config.num_queries
+        );
+        assert_eq!(
+            SecurityLevel::Bits80.queries_needed(17, DOMAIN_TRACE.len() - 1, 2),
+            config.num_queries
+        );
+
+        let proof = generate_proof_with_config(&[], &config).unwrap();
+        assert_eq!(proof.query_phase.len(), config.num_queries);
+        assert!(verify_with_config(&proof, &[], &VerifierConfig::from(&config)).is_ok());
+
+        // Only the blowup factor of our domains is supported
+        for blowup in [0, 1, 4] {
+            assert!(matches!(
+                ProverConfig::with_security_level(80, DOMAIN_TRACE.len() - 1, blowup),
+                Err(ProverError::DomainSizeMismatch)
+            ));
+        }
+    }
+
+    #[test]
+    pub fn estimate_prover_work_default() {
+        let config = ProverConfig::default();
+
+        let estimate =
+            estimate_prover_work(DOMAIN_TRACE.len(), config.blowup_factor, config.num_queries);
+
+        // 4^3 + 8 * 4
+        assert_eq!(estimate.lagrange_interp_ops, 96);
+        // Trace LDE and composition polynomial LDE (15 nodes each), and the
+        // FRI layer of degree 1 (7 nodes)
+        assert_eq!(estimate.merkle_hash_ops, 37);
+        // Folds onto domains of size 4 and 2, and 2 layers per query
+        assert_eq!(estimate.fri_ops, 8);
+
+        let more_queries = estimate_prover_work(DOMAIN_TRACE.len(), config.blowup_factor, 10);
+        assert!(more_queries.fri_ops > estimate.fri_ops);
+        assert_eq!(more_queries.merkle_hash_ops, estimate.merkle_hash_ops);
+    }
+
+    #[test]
+    pub fn grinding() {
+        let config = ProverConfig {
+            grinding_bits: 8,
+            ..Default::default()
+        };
+        let verifier_config = VerifierConfig::from(&config);
+
+        let proof = generate_proof_with_config(&[], &config).unwrap();
+        assert!(proof.grind_nonce.is_some());
+        assert!(verify_with_config(&proof, &[], &verifier_config).is_ok());
+
+        // The verifier requires its own number of bits, whatever the proof
+        assert_eq!(
+            verify(&proof, &[]),
+            Err(VerificationError::GrindingCheckFailed)
+        );
+
+        // Without grinding, there is no nonce
+        assert!(generate_proof(&[]).grind_nonce.is_none());
+
+        let mut missing_nonce = proof.clone();
+        missing_nonce.grind_nonce = None;
+        assert_eq!(
+            verify_with_config(&missing_nonce, &[], &verifier_config),
+            Err(VerificationError::GrindingCheckFailed)
+        );
+
+        // The nonce found is the smallest valid one, so 0 is invalid unless it
+        // is the nonce itself
+        if proof.grind_nonce != Some(0) {
+            let mut wrong_nonce = proof.clone();
+            wrong_nonce.grind_nonce = Some(0);
+            assert_eq!(
+                verify_with_config(&wrong_nonce, &[], &verifier_config),
+                Err(VerificationError::GrindingCheckFailed)
+            );
+        }
+    }
+
+    #[test]
+    pub fn nonce_without_grinding_rejected() {
+        // Any nonce passes a 0-bit check, so a nonce would let the prover
+        // reroll the query indices for free
+        let mut proof = generate_proof(&[]);
+        assert!(verify(&proof, &[]).is_ok());
+
+        proof.grind_nonce = Some(42);
+        assert_eq!(
+            verify(&proof, &[]),
+            Err(VerificationError::GrindingCheckFailed)
+        );
+    }
+
+    #[test]
+    pub fn merkle_path_construction_failed_display() {
+        let trace_lde = DOMAIN_LDE.to_vec();
+        // A tree of the first half of the trace LDE, where trace(gx) is out of
+        // bounds
+        let trace_tree = MerkleTree::new(&trace_lde[..4]);
+
+        let err = generate_query_phase(2, &trace_lde, &trace_tree).unwrap_err();
+        assert!(matches!(err, ProverError::MerklePathConstructionFailed(_)));
+        assert!(err
+            .to_string()
+            .starts_with("failed to construct the Merkle path of a query: "));
+    }
+
+    #[test]
+    pub fn unsupported_config() {
+        let result = generate_proof_with_config(
+            &[],
+            &ProverConfig {
+                blowup_factor: 4,
+                ..Default::default()
+            },
+        );
+        assert!(matches!(result, Err(ProverError::DomainSizeMismatch)));
+
+        let result = generate_proof_with_config(
+            &[],
+            &ProverConfig {
+                grinding_bits: MAX_GRINDING_BITS + 1,
+                ..Default::default()
+            },
+        );
+        assert!(matches!(result, Err(ProverError::UnsupportedConfig)));
+
+        let result = generate_proof_with_config(
+            &[],
+            &ProverConfig {
+                num_queries: 0,
+                ..Default::default()
+            },
+        );
+        assert!(matches!(result, Err(ProverError::UnsupportedConfig)));
+    }
+
+    #[test]
+    pub fn invalid_trace() {
+        let trace = Trace::from_column(vec![3.into(), 9.into(), 14.into(), 16.into()]);
+
+        let result = generate_proof_for_trace(&trace, 3.into(), &[], &ProverConfig::default());
+        assert!(matches!(result, Err(ProverError::TraceConstraintViolated)));
+    }
+
+    #[test]
+    pub fn fri_final_layer_inconsistent() {
+        let domain = DynamicDomain::from(&DOMAIN_TRACE).halve();
+        assert_eq!(domain.to_vec(), vec![BaseField::one(), BaseField::from(16)]);
+
+        let constant = Polynomial::new(vec![5.into()]);
+        assert_eq!(
+            fri_final_layer_value(&FriLayer::new(
+                domain.clone(),
+                constant,
+                HashAlgorithm::default()
+            ))
+            .unwrap(),
+            BaseField::from(5)
+        );
+
+        // A polynomial of degree 1 takes different values at 1 and -1
+        let degree_1 = Polynomial::new(vec![5.into(), 2.into()]);
+        assert!(matches!(
+            fri_final_layer_value(&FriLayer::new(domain, degree_1, HashAlgorithm::default())),
+            Err(ProverError::FriFinalLayerInconsistent)
+        ));
     }
 }
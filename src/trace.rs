@@ -1,18 +1,232 @@
-use crate::field::BaseField;
+use anyhow::bail;
+
+use crate::{domain::DOMAIN_TRACE, field::BaseField, poly::Polynomial};
 
 /// First element of the trace, as defined by the statement to prove.
 pub const TRACE_FIRST_ELEMENT: BaseField = BaseField::new(3);
 
+/// The execution trace, stored column by column. All columns have the same
+/// length (i.e. the number of rows of the trace).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Trace {
+    columns: Vec<Vec<BaseField>>,
+}
+
+impl Trace {
+    /// Errors if the columns don't all have the same length.
+    pub fn new(columns: Vec<Vec<BaseField>>) -> anyhow::Result<Self> {
+        if let Some(first_column) = columns.first() {
+            if columns
+                .iter()
+                .any(|column| column.len() != first_column.len())
+            {
+                bail!("all columns of the trace must have the same length");
+            }
+        }
+
+        Ok(Self { columns })
+    }
+
+    /// Builds a trace with `len` rows and `num_cols` columns, where `f(row,
+    /// col)` is the value at row `row` of column `col`.
+    pub fn from_fn(len: usize, num_cols: usize, f: impl Fn(usize, usize) -> BaseField) -> Self {
+        let columns = (0..num_cols)
+            .map(|col| (0..len).map(|row| f(row, col)).collect())
+            .collect();
+
+        Self { columns }
+    }
+
+    /// Builds a trace with a single column.
+    pub fn from_column(column: Vec<BaseField>) -> Self {
+        Self {
+            columns: vec![column],
+        }
+    }
+
+    pub fn num_columns(&self) -> usize {
+        self.columns.len()
+    }
+
+    /// The number of rows in the trace
+    pub fn len(&self) -> usize {
+        self.columns.first().map_or(0, |column| column.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn column(&self, idx: usize) -> &[BaseField] {
+        &self.columns[idx]
+    }
+
+    /// Returns the values of every column at row `row`.
+    pub fn row(&self, row: usize) -> Vec<BaseField> {
+        self.columns.iter().map(|column| column[row]).collect()
+    }
+
+    /// Same as `column()`
+    pub fn as_column_slice(&self, col: usize) -> &[BaseField] {
+        self.column(col)
+    }
+
+    /// Performs the low-degree extension of the trace. That is, interpolates
+    /// each column over `DOMAIN_TRACE`, and evaluates the resulting polynomial
+    /// over `lde_domain`.
+    ///
+    /// Errors if the trace doesn't have as many rows as `DOMAIN_TRACE` has
+    /// elements, or if `lde_domain` is smaller than the trace domain.
+    pub fn extend_lde(&self, lde_domain: &[BaseField]) -> anyhow::Result<Trace> {
+        if self.len() != DOMAIN_TRACE.len() {
+            bail!(
+                "trace has {} rows, but the trace domain has {} elements",
+                self.len(),
+                DOMAIN_TRACE.len()
+            );
+        }
+        if DOMAIN_TRACE.len() > lde_domain.len() {
+            bail!(
+                "trace domain ({} elements) is larger than the LDE domain ({} elements)",
+                DOMAIN_TRACE.len(),
+                lde_domain.len()
+            );
+        }
+
+        let columns = self
+            .columns
+            .iter()
+            .map(|column| {
+                let column_polynomial = Polynomial::lagrange_interp(&DOMAIN_TRACE, column)?;
+
+                Ok(column_polynomial.eval_domain(lde_domain))
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        Ok(Trace { columns })
+    }
+}
+
 /// The trace is 4 elements long so that we can use a small subgroup as domain,
-/// and also be able to extend it to a domain of size 8
-pub fn generate_trace() -> Vec<BaseField> {
-    let mut out_trace = vec![TRACE_FIRST_ELEMENT];
-    let mut last_ele = TRACE_FIRST_ELEMENT;
+/// and also be able to extend it to a domain of size 8. It has a single column.
+pub fn generate_trace() -> Trace {
+    Trace::from_column(squaring_trace(TRACE_FIRST_ELEMENT, 4))
+}
 
-    for _i in 0..3 {
-        last_ele = last_ele.square();
+/// Generates the sequence `a_0 = first_element`, `a_{n+1} = (a_n)^2` of length
+/// `len`.
+pub fn squaring_trace(first_element: BaseField, len: usize) -> Vec<BaseField> {
+    generate_power_trace(first_element, 2, len)
+}
+
+/// Generates the sequence `a_0 = first_element`, `a_{n+1} = (a_n)^power` of
+/// length `len`.
+pub fn generate_power_trace(first_element: BaseField, power: u8, len: usize) -> Vec<BaseField> {
+    let mut out_trace = vec![first_element];
+    let mut last_ele = first_element;
+
+    for _i in 1..len {
+        last_ele = last_ele.exp(power);
         out_trace.push(last_ele);
     }
 
     out_trace
 }
+
+/// Returns every squaring trace of length `len` (see `squaring_trace()`); that
+/// is, one for each nonzero first element. This is useful for exhaustive
+/// testing.
+pub fn all_squaring_traces(len: usize) -> impl Iterator<Item = Vec<BaseField>> {
+    (1..17u8).map(move |start| generate_power_trace(start.into(), 2, len))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        domain::DOMAIN_LDE,
+        prover::{generate_proof_for_trace, ProverConfig},
+        verifier::verify_with_first_element,
+    };
+
+    #[test]
+    pub fn two_column_trace() {
+        let squares = squaring_trace(TRACE_FIRST_ELEMENT, 4);
+        let counter: Vec<BaseField> = (0..4).map(BaseField::from).collect();
+
+        let trace = Trace::new(vec![squares.clone(), counter.clone()]).unwrap();
+
+        assert_eq!(trace.num_columns(), 2);
+        assert_eq!(trace.len(), 4);
+        assert_eq!(trace.column(0), &squares[..]);
+        assert_eq!(trace.as_column_slice(1), &counter[..]);
+        assert_eq!(trace.row(1), vec![squares[1], counter[1]]);
+
+        assert!(Trace::new(vec![squares, vec![BaseField::zero()]]).is_err());
+    }
+
+    #[test]
+    pub fn generate_trace_single_column() {
+        let trace = generate_trace();
+
+        assert_eq!(trace.num_columns(), 1);
+        assert_eq!(trace.column(0), &[3.into(), 9.into(), 13.into(), 16.into()]);
+    }
+
+    #[test]
+    pub fn from_fn_squaring_chain() {
+        // The element at row `i` is `first_element^(2^i)`
+        let trace = Trace::from_fn(4, 1, |row, _| TRACE_FIRST_ELEMENT.exp(1 << row));
+
+        assert_eq!(trace, generate_trace());
+    }
+
+    #[test]
+    pub fn from_fn_two_columns() {
+        let trace = Trace::from_fn(4, 2, |row, col| BaseField::from((row + 4 * col) as u8));
+
+        assert_eq!(trace.num_columns(), 2);
+        assert_eq!(trace.len(), 4);
+        assert_eq!(trace.column(0), &[0.into(), 1.into(), 2.into(), 3.into()]);
+        assert_eq!(trace.column(1), &[4.into(), 5.into(), 6.into(), 7.into()]);
+    }
+
+    #[test]
+    pub fn extend_lde_matches_prover() {
+        let trace = generate_trace();
+
+        let trace_polynomial = Polynomial::lagrange_interp(&DOMAIN_TRACE, trace.column(0)).unwrap();
+        let trace_lde = trace_polynomial.eval_domain(&DOMAIN_LDE);
+
+        let extended_trace = trace.extend_lde(&DOMAIN_LDE).unwrap();
+        assert_eq!(extended_trace.num_columns(), 1);
+        assert_eq!(extended_trace.column(0), &trace_lde[..]);
+
+        // The LDE domain can't be smaller than the trace domain
+        let small_domain: Vec<BaseField> = DOMAIN_LDE.iter().take(2).copied().collect();
+        assert!(trace.extend_lde(&small_domain).is_err());
+    }
+
+    #[test]
+    pub fn all_squaring_traces_prove_and_verify() {
+        let traces: Vec<Vec<BaseField>> = all_squaring_traces(DOMAIN_TRACE.len()).collect();
+        assert_eq!(traces.len(), 16);
+
+        for trace in traces {
+            let first_element = trace[0];
+            let proof = generate_proof_for_trace(
+                &Trace::from_column(trace),
+                first_element,
+                &[],
+                &ProverConfig::default(),
+            )
+            .unwrap();
+
+            assert!(
+                verify_with_first_element(&proof, first_element, &[], &Default::default()).is_ok(),
+                "first element {first_element}"
+            );
+        }
+    }
+}
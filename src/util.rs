@@ -1,3 +1,5 @@
+use crate::field::BaseField;
+
 pub fn is_power_of_2(n: usize) -> bool {
     if n == 0 {
         false
@@ -6,3 +8,102 @@ pub fn is_power_of_2(n: usize) -> bool {
         (n & (n - 1)) == 0
     }
 }
+
+/// Computes `ceil(a / b)`. Panics if `b` is 0.
+pub fn ceil_div(a: usize, b: usize) -> usize {
+    assert_ne!(b, 0, "division by zero");
+
+    a.div_ceil(b)
+}
+
+/// Computes `floor(a / b)`. Panics if `b` is 0.
+pub fn floor_div(a: usize, b: usize) -> usize {
+    assert_ne!(b, 0, "division by zero");
+
+    a / b
+}
+
+/// Counts the number of set bits in `x`
+pub fn hamming_weight(x: u8) -> u32 {
+    x.count_ones()
+}
+
+/// Counts the number of bits that differ between `a` and `b`
+pub fn hamming_distance(a: u8, b: u8) -> u32 {
+    hamming_weight(a ^ b)
+}
+
+/// Computes the empirical Shannon entropy (in bits) of the distribution of
+/// `samples`. A uniform distribution over `BaseField` has entropy `log2(17) ≈
+/// 4.09` bits.
+pub fn entropy_estimate(samples: &[BaseField]) -> f64 {
+    let mut counts = [0usize; 256];
+    for sample in samples {
+        counts[sample.as_byte() as usize] += 1;
+    }
+
+    let num_samples = samples.len() as f64;
+
+    counts
+        .iter()
+        .filter(|count| **count > 0)
+        .map(|count| {
+            let probability = *count as f64 / num_samples;
+
+            -probability * probability.log2()
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::channel::Channel;
+
+    #[test]
+    pub fn test_ceil_floor_div() {
+        assert_eq!(ceil_div(5, 2), 3);
+        assert_eq!(floor_div(5, 2), 2);
+        assert_eq!(ceil_div(4, 2), 2);
+        assert_eq!(floor_div(4, 2), 2);
+        assert_eq!(ceil_div(0, 3), 0);
+    }
+
+    #[test]
+    #[should_panic]
+    pub fn test_ceil_div_by_zero() {
+        ceil_div(1, 0);
+    }
+
+    #[test]
+    pub fn test_hamming_weight() {
+        assert_eq!(hamming_weight(0), 0);
+        assert_eq!(hamming_weight(1), 1);
+        assert_eq!(hamming_weight(17), 2);
+        assert_eq!(hamming_weight(255), 8);
+    }
+
+    #[test]
+    pub fn test_hamming_distance() {
+        assert_eq!(hamming_distance(0, 0), 0);
+        assert_eq!(hamming_distance(255, 255), 0);
+        assert_eq!(hamming_distance(0, 255), 8);
+        assert_eq!(hamming_distance(3, 5), 2);
+    }
+
+    #[test]
+    pub fn test_entropy_estimate() {
+        assert_eq!(entropy_estimate(&[BaseField::one(); 10]), 0.0);
+        assert_eq!(
+            entropy_estimate(&[BaseField::zero(), BaseField::one()]),
+            1.0
+        );
+
+        let mut channel = Channel::new_empty();
+        let samples: Vec<BaseField> = (0..1000).map(|_| channel.random_element()).collect();
+
+        let entropy = entropy_estimate(&samples);
+        assert!((entropy - 17f64.log2()).abs() < 0.05, "entropy: {entropy}");
+    }
+}
@@ -1,15 +1,109 @@
-use anyhow::bail;
+use std::fmt::Display;
 
 use crate::{
-    channel::Channel,
-    domain::{DOMAIN_LDE, DOMAIN_TRACE},
-    field::BaseField,
-    trace::TRACE_FIRST_ELEMENT,
+    channel::Channel, constraints::composition_poly_at_x, domain::DOMAIN_LDE, field::BaseField,
+    fri::verify_fri_with_hash, merkle::MerklePath, poly::Polynomial, trace::TRACE_FIRST_ELEMENT,
     ProofQueryPhase, StarkProof,
 };
 
-pub fn verify(stark_proof: &StarkProof) -> anyhow::Result<()> {
-    let mut channel = Channel::new();
+/// The verifier evaluates the boundary constraint at each query
+pub use crate::constraints::check_boundary_constraint;
+
+/// The reasons why the verifier can reject a proof
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VerificationError {
+    /// A queried value is not in the committed Merkle tree. `which` identifies
+    /// the queried value (e.g. "trace_x").
+    MerkleProofFailed { which: &'static str },
+    /// The value of the last FRI layer is not consistent with the previous
+    /// layers.
+    FriCheckFailed,
+    /// The composition polynomial is not consistent with the trace.
+    ConstraintCheckFailed,
+    /// The queries in the proof don't match the ones drawn by the verifier.
+    QueryIndexMismatch,
+    /// The proof contains no queries (or the verifier asks for none), so
+    /// nothing ties its commitments to the statement.
+    NoQueries,
+    /// The proof-of-work nonce is missing, or doesn't have the required number
+    /// of leading zero bits.
+    GrindingCheckFailed,
+    /// The proof was generated for an unsupported version of the protocol.
+    UnsupportedVersion(u32),
+}
+
+impl Display for VerificationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VerificationError::MerkleProofFailed { which } => {
+                write!(f, "{which} merkle proof verification failed")
+            }
+            VerificationError::FriCheckFailed => write!(f, "FRI check failed"),
+            VerificationError::ConstraintCheckFailed => write!(f, "constraint check failed"),
+            VerificationError::QueryIndexMismatch => {
+                write!(f, "queries don't match the expected query indices")
+            }
+            VerificationError::NoQueries => write!(f, "no queries to check"),
+            VerificationError::GrindingCheckFailed => write!(f, "proof-of-work check failed"),
+            VerificationError::UnsupportedVersion(version) => {
+                write!(f, "unsupported proof version {version}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for VerificationError {}
+
+/// The parameters that the verifier requires the proof to be generated with.
+/// These must come from the verifier, and not from the proof: a cheating
+/// prover would simply pick the parameters that make cheating easiest.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VerifierConfig {
+    /// The number of queries that the proof must contain. Accepting fewer
+    /// would let a cheating prover send a single query, whatever the number
+    /// of queries the protocol asks for. See `ProverConfig::num_queries`.
+    pub num_queries: usize,
+    /// The number of bits of proof-of-work that the proof must contain. See
+    /// `ProverConfig::grinding_bits`.
+    pub grinding_bits: u32,
+}
+
+impl Default for VerifierConfig {
+    /// Matches `ProverConfig::default()`
+    fn default() -> Self {
+        Self {
+            num_queries: 1,
+            grinding_bits: 0,
+        }
+    }
+}
+
+/// Verifies the proof for the statement that the trace starts with
+/// `TRACE_FIRST_ELEMENT`, with the default `VerifierConfig`. `pub_inputs` must
+/// be the public inputs that the proof was generated with.
+pub fn verify(stark_proof: &StarkProof, pub_inputs: &[u8]) -> Result<(), VerificationError> {
+    verify_with_config(stark_proof, pub_inputs, &VerifierConfig::default())
+}
+
+/// Same as `verify()`, but requires the proof to satisfy `config`
+pub fn verify_with_config(
+    stark_proof: &StarkProof,
+    pub_inputs: &[u8],
+    config: &VerifierConfig,
+) -> Result<(), VerificationError> {
+    verify_with_first_element(stark_proof, TRACE_FIRST_ELEMENT, pub_inputs, config)
+}
+
+/// Verifies the proof for the statement that the trace starts with
+/// `first_element` (and that every subsequent element is the square of the
+/// previous one).
+pub fn verify_with_first_element(
+    stark_proof: &StarkProof,
+    first_element: BaseField,
+    pub_inputs: &[u8],
+    config: &VerifierConfig,
+) -> Result<(), VerificationError> {
+    let mut channel = Channel::new_with_hash(stark_proof.hash_algorithm, pub_inputs);
 
     // We interact with the channel in the exact same way the prover does, in
     // order to draw the same values the prover did when generating the proof.
@@ -20,124 +114,370 @@ pub fn verify(stark_proof: &StarkProof) -> anyhow::Result<()> {
 
     channel.commit(stark_proof.composition_poly_lde_commitment);
 
-    let beta_fri_deg_1 = channel.random_element();
-    channel.commit(stark_proof.fri_layer_deg_1_commitment);
+    // The composition polynomial has degree 3, so it takes 2 folds to get down
+    // to a constant; only the layer of degree 1 in between is committed. More
+    // folds would accept polynomials of higher degree.
+    if stark_proof.fri.commitment.layer_roots.len() != 1 {
+        return Err(VerificationError::FriCheckFailed);
+    }
 
-    let beta_fri_deg_0 = channel.random_element();
+    let mut betas = vec![channel.random_element()];
+    for layer_root in stark_proof.fri.commitment.layer_roots.iter() {
+        channel.commit(*layer_root);
+        betas.push(channel.random_element());
+    }
 
-    let query_idx = channel.random_integer(DOMAIN_LDE.len() as u8 - 2) as usize;
+    // Proof-of-work. A nonce without grinding would be mixed into the channel
+    // anyway, letting the prover try nonces for free until it gets query
+    // indices that suit it.
+    let grinding_ok = match (stark_proof.grind_nonce, config.grinding_bits) {
+        (None, bits) => bits == 0,
+        (Some(nonce), bits) => (1..=64).contains(&bits) && channel.verify_grind(nonce, bits),
+    };
+    if !grinding_ok {
+        return Err(VerificationError::GrindingCheckFailed);
+    }
 
-    // Verify all the Merkle proofs, to make sure that values in the proof
-    // struct are valid.
-    verify_merkle_proofs(stark_proof)?;
+    if config.num_queries == 0 || stark_proof.query_phase.is_empty() {
+        return Err(VerificationError::NoQueries);
+    }
+    if stark_proof.query_phase.len() != config.num_queries
+        || stark_proof.fri.query_paths.len() != config.num_queries
+    {
+        return Err(VerificationError::QueryIndexMismatch);
+    }
 
-    verify_query(
-        &stark_proof.query_phase,
-        alpha_0,
-        alpha_1,
-        beta_fri_deg_1,
-        beta_fri_deg_0,
-        query_idx,
+    let mut query_indices = Vec::with_capacity(config.num_queries);
+    for (query, fri_openings) in stark_proof
+        .query_phase
+        .iter()
+        .zip(stark_proof.fri.query_paths.iter())
+    {
+        let query_idx = channel.random_u64(DOMAIN_LDE.len() as u64 - 2) as usize;
+        query_indices.push(query_idx);
+
+        // Verify all the Merkle proofs, to make sure that values in the proof
+        // struct are valid.
+        verify_merkle_proofs(stark_proof, query, query_idx)?;
+
+        // The first value opened by FRI is the composition polynomial at x
+        let Some((cp_x, cp_x_path)) = fri_openings.first() else {
+            return Err(VerificationError::FriCheckFailed);
+        };
+
+        verify_query(
+            query,
+            *cp_x,
+            cp_x_path,
+            first_element,
+            alpha_0,
+            alpha_1,
+            query_idx,
+        )?;
+    }
+
+    // Checks the Merkle proofs of the FRI openings (including `cp_x` above),
+    // and that they fold down to the value of the last layer.
+    verify_fri_with_hash(
+        &stark_proof.fri,
+        stark_proof.composition_poly_lde_commitment,
+        &betas,
+        &query_indices,
+        stark_proof.hash_algorithm,
     )
 }
 
-fn verify_merkle_proofs(stark_proof: &StarkProof) -> anyhow::Result<()> {
+fn verify_merkle_proofs(
+    stark_proof: &StarkProof,
+    query: &ProofQueryPhase,
+    query_idx: usize,
+) -> Result<(), VerificationError> {
+    // The paths must open the trace LDE at x and gx, not at some other point
+    // of the prover's choosing. g is the generator of the trace domain, which
+    // is 2 steps away from x in the LDE domain.
+    let depth = DOMAIN_LDE.len().ilog2() as usize;
+    let opens_at = |merkle_proof: &MerklePath, idx: usize| {
+        merkle_proof.len() == depth && merkle_proof.leaf_index() == idx
+    };
+    if !opens_at(&query.trace_x.1, query_idx) || !opens_at(&query.trace_gx.1, query_idx + 2) {
+        return Err(VerificationError::QueryIndexMismatch);
+    }
+
     // trace(x)
     {
-        let (value, merkle_proof) = &stark_proof.query_phase.trace_x;
+        let (value, merkle_proof) = &query.trace_x;
         let root = stark_proof.trace_lde_commitment;
-        if !merkle_proof.verify_inclusion(*value, root) {
-            bail!("trace_x merkle proof verification failed");
+        if !merkle_proof.verify_inclusion_with_hash(*value, root, stark_proof.hash_algorithm) {
+            return Err(VerificationError::MerkleProofFailed { which: "trace_x" });
         }
     }
 
     // trace(gx)
     {
-        let (value, merkle_proof) = &stark_proof.query_phase.trace_gx;
+        let (value, merkle_proof) = &query.trace_gx;
         let root = stark_proof.trace_lde_commitment;
-        if !merkle_proof.verify_inclusion(*value, root) {
-            bail!("trace_gx merkle proof verification failed");
+        if !merkle_proof.verify_inclusion_with_hash(*value, root, stark_proof.hash_algorithm) {
+            return Err(VerificationError::MerkleProofFailed { which: "trace_gx" });
         }
     }
 
-    // cp(-x)
-    {
-        let (value, merkle_proof) = &stark_proof.query_phase.cp_minus_x;
-        let root = stark_proof.composition_poly_lde_commitment;
-        if !merkle_proof.verify_inclusion(*value, root) {
-            bail!("cp_minus_x merkle proof verification failed");
-        }
+    Ok(())
+}
+
+/// Checks that the points in `values` all lie on a polynomial of degree at most
+/// `degree_bound`, by interpolating all of them. This is what FRI checks
+/// probabilistically, using only a few queries; here, we need all the
+/// evaluations, which is exactly what FRI allows the verifier to avoid. It
+/// serves as a reference to test FRI against.
+///
+/// Precondition: the points have distinct `x` coordinates
+pub fn check_low_degree_via_interpolation(
+    values: &[(BaseField, BaseField)],
+    degree_bound: usize,
+) -> bool {
+    let (domain, evaluations): (Vec<BaseField>, Vec<BaseField>) = values.iter().copied().unzip();
+
+    match Polynomial::lagrange_interp(&domain, &evaluations) {
+        Ok(interpolated_poly) => interpolated_poly.trimmed().degree() <= degree_bound,
+        Err(_) => false,
     }
+}
 
-    // FRI layer degree 1 at -x^2
-    {
-        let (value, merkle_proof) = &stark_proof.query_phase.fri_layer_deg_1_minus_x;
-        let root = stark_proof.fri_layer_deg_1_commitment;
-        if !merkle_proof.verify_inclusion(*value, root) {
-            bail!("fri_layer_deg_1_minus_x merkle proof verification failed");
-        }
+/// Checks whether some polynomial of degree at most `max_degree` takes the
+/// value `trace_x` at `x`, and `trace_gx` at `g * x`, as revealed in the query
+/// phase.
+///
+/// Note that two points always determine a polynomial of degree at most 1, so
+/// this check always passes for `max_degree >= 1` (as long as `x != g * x`).
+/// It gives no evidence whatsoever that the trace polynomial is low-degree;
+/// only a low-degree test such as FRI can do that.
+pub fn spot_check_trace_polynomial(
+    trace_x: BaseField,
+    trace_gx: BaseField,
+    x: BaseField,
+    g: BaseField,
+    max_degree: usize,
+) -> bool {
+    if x == g * x {
+        // Both evaluations are at the same point
+        return trace_x == trace_gx;
     }
 
-    Ok(())
+    // A constant polynomial is the only one of degree 0
+    max_degree >= 1 || trace_x == trace_gx
 }
 
+/// Checks that the composition polynomial value opened by FRI at the query
+/// index is actually derived from the trace.
 fn verify_query(
     queries: &ProofQueryPhase,
+    cp_x: BaseField,
+    cp_x_path: &MerklePath,
+    first_element: BaseField,
     alpha_0: BaseField,
     alpha_1: BaseField,
-    beta_fri_deg_1: BaseField,
-    beta_fri_deg_0: BaseField,
     query_idx: usize,
-) -> anyhow::Result<()> {
+) -> Result<(), VerificationError> {
+    // FRI must open the composition polynomial at the query index
+    if cp_x_path.len() != DOMAIN_LDE.len().ilog2() as usize || cp_x_path.leaf_index() != query_idx {
+        return Err(VerificationError::QueryIndexMismatch);
+    }
+
     let x = DOMAIN_LDE[query_idx];
 
-    // Ensure that the composition polynomial value is actually derived from the trace
-    let boundary_constraint_x: BaseField = {
-        let p1_x = queries.trace_x.0 - TRACE_FIRST_ELEMENT;
+    let expected_cp_x = composition_poly_at_x(
+        x,
+        queries.trace_x.0,
+        queries.trace_gx.0,
+        first_element,
+        alpha_0,
+        alpha_1,
+    );
 
-        p1_x / (x - DOMAIN_TRACE[0])
+    if expected_cp_x == cp_x {
+        Ok(())
+    } else {
+        Err(VerificationError::ConstraintCheckFailed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        constraints::{boundary_constraint, composition_polynomial},
+        domain::DOMAIN_TRACE,
+        fri::build_all_layers,
+        prover::generate_proof,
     };
 
-    let transition_constraint_x: BaseField = {
-        let p2_x = queries.trace_gx.0 - queries.trace_x.0.exp(2);
+    #[test]
+    pub fn boundary_constraint_at_query() {
+        let proof = generate_proof(&[]);
 
-        let denom = (x - DOMAIN_TRACE[0]) * (x - DOMAIN_TRACE[1]) * (x - DOMAIN_TRACE[2]);
+        // Replay the channel to recover the query index
+        let query_idx = {
+            let mut channel = Channel::new(&[]);
+            channel.commit(proof.trace_lde_commitment);
+            channel.random_element();
+            channel.random_element();
+            channel.commit(proof.composition_poly_lde_commitment);
+            channel.random_element();
+            channel.commit(proof.fri.commitment.layer_roots[0]);
+            channel.random_element();
 
-        p2_x / denom
-    };
+            channel.random_u64(DOMAIN_LDE.len() as u64 - 2) as usize
+        };
+        let x = DOMAIN_LDE[query_idx];
 
-    // composition_polynomial(x)
-    let cp_x = boundary_constraint_x * alpha_0 + transition_constraint_x * alpha_1;
+        let boundary_constraint_x = check_boundary_constraint(
+            x,
+            proof.query_phase[0].trace_x.0,
+            TRACE_FIRST_ELEMENT,
+            DOMAIN_TRACE[0],
+        );
 
-    // FRI layer deg 1
-    let fri_layer_deg_1_x: BaseField = {
-        let cp_minus_x = queries.cp_minus_x.0;
+        assert_eq!(
+            boundary_constraint_x,
+            boundary_constraint(TRACE_FIRST_ELEMENT, &DOMAIN_TRACE).eval(x)
+        );
+    }
 
-        let g_x_squared = (cp_x + cp_minus_x) / BaseField::from(2);
-        let h_x_squared = (cp_x - cp_minus_x) / (BaseField::from(2) * x);
+    #[test]
+    pub fn tampered_merkle_proof_error() {
+        let mut proof = generate_proof(&[]);
+        // cp(-x) is the second value opened by FRI
+        proof.fri.query_paths[0][1].0 += BaseField::one();
 
-        g_x_squared + beta_fri_deg_1 * h_x_squared
-    };
+        assert_eq!(
+            verify(&proof, &[]),
+            Err(VerificationError::MerkleProofFailed {
+                which: "cp_minus_x"
+            })
+        );
+    }
 
-    // FRI layer deg 0
-    let x = x.exp(2);
+    #[test]
+    pub fn trace_path_for_another_index_error() {
+        let mut proof = generate_proof(&[]);
+        // trace(gx) is a valid opening of the trace commitment, but not at x
+        proof.query_phase[0].trace_x = proof.query_phase[0].trace_gx.clone();
+        assert!(proof.query_phase[0].trace_x.1.verify_inclusion_with_hash(
+            proof.query_phase[0].trace_x.0,
+            proof.trace_lde_commitment,
+            proof.hash_algorithm
+        ));
 
-    let expected_fri_layer_deg_0_x: BaseField = {
-        let fri_layer_deg_1_minus_x = queries.fri_layer_deg_1_minus_x.0;
+        assert_eq!(
+            verify(&proof, &[]),
+            Err(VerificationError::QueryIndexMismatch)
+        );
+    }
 
-        let g_x_squared = (fri_layer_deg_1_x + fri_layer_deg_1_minus_x) / BaseField::from(2);
-        let h_x_squared = (fri_layer_deg_1_x - fri_layer_deg_1_minus_x) / (BaseField::from(2) * x);
+    #[test]
+    pub fn no_queries_error() {
+        let proof = generate_proof(&[]);
 
-        g_x_squared + beta_fri_deg_0 * h_x_squared
-    };
+        let mut tampered_proof = proof.clone();
+        tampered_proof.query_phase.clear();
+        tampered_proof.fri.query_paths.clear();
+        assert_eq!(
+            verify(&tampered_proof, &[]),
+            Err(VerificationError::NoQueries)
+        );
 
-    if expected_fri_layer_deg_0_x == queries.fri_layer_deg_0_x {
-        Ok(())
-    } else {
-        bail!(
-            "Final FRI layer check failed. Value in proof: {}, but computed {}",
-            queries.fri_layer_deg_0_x,
-            expected_fri_layer_deg_0_x
-        )
+        assert_eq!(
+            verify_with_config(
+                &proof,
+                &[],
+                &VerifierConfig {
+                    num_queries: 0,
+                    ..Default::default()
+                }
+            ),
+            Err(VerificationError::NoQueries)
+        );
+    }
+
+    #[test]
+    pub fn tampered_fri_layer_error() {
+        let mut proof = generate_proof(&[]);
+        proof.fri.commitment.final_value += BaseField::one();
+
+        assert_eq!(verify(&proof, &[]), Err(VerificationError::FriCheckFailed));
+    }
+
+    #[test]
+    pub fn wrong_number_of_fri_layers_error() {
+        let proof = generate_proof(&[]);
+
+        let mut tampered_proof = proof.clone();
+        tampered_proof.fri.commitment.layer_roots.clear();
+        assert_eq!(
+            verify(&tampered_proof, &[]),
+            Err(VerificationError::FriCheckFailed)
+        );
+
+        let mut tampered_proof = proof;
+        let layer_root = tampered_proof.fri.commitment.layer_roots[0];
+        tampered_proof.fri.commitment.layer_roots.push(layer_root);
+        assert_eq!(
+            verify(&tampered_proof, &[]),
+            Err(VerificationError::FriCheckFailed)
+        );
+    }
+
+    #[test]
+    pub fn low_degree_via_interpolation_agrees_with_fri() {
+        let betas: [BaseField; 2] = [3.into(), 7.into()];
+
+        // Valid claim: the composition polynomial has degree 3
+        let cp = composition_polynomial(TRACE_FIRST_ELEMENT, 5.into(), 11.into());
+        let cp_lde = cp.eval_domain(&DOMAIN_LDE);
+        let values: Vec<(BaseField, BaseField)> =
+            DOMAIN_LDE.iter().copied().zip(cp_lde.clone()).collect();
+
+        let fri_layers = build_all_layers(cp, &(&DOMAIN_LDE).into(), &betas);
+        let last_layer = &fri_layers.last().unwrap().evaluations;
+        assert_eq!(last_layer[0], last_layer[1]);
+        assert!(check_low_degree_via_interpolation(&values, 3));
+
+        // Invalid claim: changing a single evaluation yields a polynomial of
+        // degree 7
+        let mut tampered_cp_lde = cp_lde;
+        tampered_cp_lde[0] += BaseField::one();
+        let tampered_values: Vec<(BaseField, BaseField)> = DOMAIN_LDE
+            .iter()
+            .copied()
+            .zip(tampered_cp_lde.clone())
+            .collect();
+        let tampered_poly = Polynomial::lagrange_interp(&DOMAIN_LDE, &tampered_cp_lde).unwrap();
+
+        let fri_layers = build_all_layers(tampered_poly, &(&DOMAIN_LDE).into(), &betas);
+        let last_layer = &fri_layers.last().unwrap().evaluations;
+        assert_ne!(last_layer[0], last_layer[1]);
+        assert!(!check_low_degree_via_interpolation(&tampered_values, 3));
+        assert!(check_low_degree_via_interpolation(&tampered_values, 7));
+    }
+
+    #[test]
+    pub fn spot_check_trace_polynomial_always_passes_above_degree_0() {
+        let g = DOMAIN_TRACE[1];
+        let x = DOMAIN_LDE[0];
+
+        assert!(spot_check_trace_polynomial(1.into(), 2.into(), x, g, 1));
+        assert!(spot_check_trace_polynomial(1.into(), 2.into(), x, g, 3));
+        assert!(!spot_check_trace_polynomial(1.into(), 2.into(), x, g, 0));
+        assert!(spot_check_trace_polynomial(5.into(), 5.into(), x, g, 0));
+
+        // Same point, different values: no polynomial fits
+        assert!(!spot_check_trace_polynomial(
+            1.into(),
+            2.into(),
+            x,
+            BaseField::one(),
+            3
+        ));
     }
 }